@@ -1,20 +1,942 @@
-#[derive(Clone, Debug)]
+/// One `name: type = value` binding of a `let`. Without a value the name is declared but
+/// unassigned until its first assignment.
+pub type Declarator = (String, Option<String>, Option<Box<Expr>>);
+
+/// A byte range of the source passed to `parse`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A node together with the source it was parsed from. It derefs to the node, so passes that
+/// don't care about positions read through it.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+// Nodes built by hand rather than parsed get an empty span.
+impl<T> From<T> for Spanned<T> {
+    fn from(node: T) -> Self {
+        Self::new(node, Span::default())
+    }
+}
+impl<T> std::ops::Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+impl<T: std::fmt::Display> std::fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
     ExprStatement(Box<Expr>),
-    Let(String, String, Box<Expr>),
-    Print(Box<Expr>)
+    /// `let a = 1, b:u8 = a + 1;` declares each name in order.
+    Let(Vec<Declarator>),
+    /// `let [a, b] = [1, 2];` binds each name to the array element in the same position.
+    Destructure(Vec<String>, Box<Expr>),
+    Const(String, String, Box<Expr>),
+    /// Arguments print space-separated on one line, like `console.log`.
+    Print(Vec<Box<Expr>>),
+    Block(Vec<Box<Spanned<Statement>>>),
+    /// `for (init; cond; step) { body }`; every clause may be left out.
+    For(
+        Option<Box<Spanned<Statement>>>,
+        Option<Box<Expr>>,
+        Option<Box<Expr>>,
+        Vec<Box<Spanned<Statement>>>,
+    ),
+    /// `do { body } while (cond);` runs the body before the first check.
+    DoWhile(Vec<Box<Spanned<Statement>>>, Box<Expr>),
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
-    Number(f64),
+    Number(Literal),
+    Str(String),
+    Bool(bool),
     Id(String),
     PI,
     E,
+    Constant(Constant),
+    Call(String, Vec<Box<Expr>>),
+    Array(Vec<Box<Expr>>),
+    /// `(a, b)`: a fixed group of values of any type. For now it can only be destructured, as in
+    /// `let [x, y] = (1, true);`.
+    Tuple(Vec<Box<Expr>>),
+    Index(Box<Expr>, Box<Expr>),
+    Length(Box<Expr>),
+    /// `` `text ${expr} text` ``: the parts are joined into one string.
+    Template(Vec<TemplatePart>),
+    /// `receiver.name(args)`.
+    Method(Box<Expr>, String, Vec<Box<Expr>>),
+    PreIncrement(String),
+    PreDecrement(String),
+    PostIncrement(String),
+    PostDecrement(String),
     Parenthesis(Box<Expr>),
     Exponent(Box<Expr>, Box<Expr>),
     Multiply(Box<Expr>, Box<Expr>),
     Divide(Box<Expr>, Box<Expr>),
     Add(Box<Expr>, Box<Expr>),
     Sub(Box<Expr>, Box<Expr>),
+    Cast(Box<Expr>, NumType),
+    /// `saturate(x, t)`: like `as`, but out-of-range integers clamp to t's bounds instead of
+    /// wrapping.
+    Saturate(Box<Expr>, NumType),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    /// The operand's type name as a string: a NumType, "bool", "string" or "array".
+    TypeOf(Box<Expr>),
+    BitNot(Box<Expr>),
+    Shl(Box<Expr>, Box<Expr>),
+    Shr(Box<Expr>, Box<Expr>),
+    Less(Box<Expr>, Box<Expr>),
+    LessEq(Box<Expr>, Box<Expr>),
+    Greater(Box<Expr>, Box<Expr>),
+    GreaterEq(Box<Expr>, Box<Expr>),
+    Equal(Box<Expr>, Box<Expr>),
+    NotEqual(Box<Expr>, Box<Expr>),
+    BitAnd(Box<Expr>, Box<Expr>),
+    BitXor(Box<Expr>, Box<Expr>),
+    BitOr(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
     Eq(String, Box<Expr>),
+}
+
+/// The named f64 constants besides PI and e.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constant {
+    Tau,
+    Sqrt2,
+    Ln2,
+    Ln10,
+    Infinity,
+    NaN,
+}
+impl Constant {
+    pub fn value(self) -> f64 {
+        match self {
+            Constant::Tau => std::f64::consts::TAU,
+            Constant::Sqrt2 => std::f64::consts::SQRT_2,
+            Constant::Ln2 => std::f64::consts::LN_2,
+            Constant::Ln10 => std::f64::consts::LN_10,
+            Constant::Infinity => f64::INFINITY,
+            Constant::NaN => f64::NAN,
+        }
+    }
+}
+impl std::fmt::Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Tau => write!(f, "TAU"),
+            Constant::Sqrt2 => write!(f, "SQRT_2"),
+            Constant::Ln2 => write!(f, "LN_2"),
+            Constant::Ln10 => write!(f, "LN_10"),
+            Constant::Infinity => write!(f, "INFINITY"),
+            Constant::NaN => write!(f, "NAN"),
+        }
+    }
+}
+
+/// A numeric literal as written. Whole numbers are kept exactly rather than as f64, which can't
+/// tell large u64s apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Literal {
+    Int(u128),
+    Float(f64),
+}
+impl Literal {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Literal::Int(n) => n as f64,
+            Literal::Float(n) => n,
+        }
+    }
+}
+// A whole float keeps its `.0`, so it parses back as a float.
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Int(n) => write!(f, "{}", n),
+            Literal::Float(n) if n.fract() == 0.0 => write!(f, "{}.0", n),
+            Literal::Float(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    Usize,
+    Isize,
+    U128,
+    I128,
+    F32,
+    F64,
+}
+impl std::fmt::Display for NumType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumType::U8 => write!(f, "u8"),
+            NumType::I8 => write!(f, "i8"),
+            NumType::U16 => write!(f, "u16"),
+            NumType::I16 => write!(f, "i16"),
+            NumType::U32 => write!(f, "u32"),
+            NumType::I32 => write!(f, "i32"),
+            NumType::U64 => write!(f, "u64"),
+            NumType::I64 => write!(f, "i64"),
+            NumType::Usize => write!(f, "usize"),
+            NumType::Isize => write!(f, "isize"),
+            NumType::U128 => write!(f, "u128"),
+            NumType::I128 => write!(f, "i128"),
+            NumType::F32 => write!(f, "f32"),
+            NumType::F64 => write!(f, "f64"),
+        }
+    }
+}
+impl std::str::FromStr for NumType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u8" => Ok(NumType::U8),
+            "i8" => Ok(NumType::I8),
+            "u16" => Ok(NumType::U16),
+            "i16" => Ok(NumType::I16),
+            "u32" => Ok(NumType::U32),
+            "i32" => Ok(NumType::I32),
+            "u64" => Ok(NumType::U64),
+            "i64" => Ok(NumType::I64),
+            "usize" => Ok(NumType::Usize),
+            "isize" => Ok(NumType::Isize),
+            "u128" => Ok(NumType::U128),
+            "i128" => Ok(NumType::I128),
+            "f32" => Ok(NumType::F32),
+            "f64" => Ok(NumType::F64),
+            _ => Err(format!("Unknown type {}.", s)),
+        }
+    }
+}
+
+impl NumType {
+    pub const ALL: [NumType; 14] = [
+        NumType::U8,
+        NumType::I8,
+        NumType::U16,
+        NumType::I16,
+        NumType::U32,
+        NumType::I32,
+        NumType::U64,
+        NumType::I64,
+        NumType::Usize,
+        NumType::Isize,
+        NumType::U128,
+        NumType::I128,
+        NumType::F32,
+        NumType::F64,
+    ];
+
+    pub fn is_float(self) -> bool {
+        matches!(self, NumType::F32 | NumType::F64)
+    }
+
+    pub fn is_integer(self) -> bool {
+        !self.is_float()
+    }
+
+    pub fn is_signed(self) -> bool {
+        !matches!(
+            self,
+            NumType::U8
+                | NumType::U16
+                | NumType::U32
+                | NumType::U64
+                | NumType::Usize
+                | NumType::U128
+        )
+    }
+
+    /// The smallest and largest values of an integer type; None for floats.
+    pub fn int_range(self) -> Option<(i128, u128)> {
+        let bits = self.bit_width();
+        match self {
+            NumType::F32 | NumType::F64 => None,
+            NumType::I128 => Some((i128::MIN, i128::MAX as u128)),
+            _ if self.is_signed() => Some((-(1 << (bits - 1)), (1 << (bits - 1)) - 1)),
+            _ => Some((0, u128::MAX >> (128 - bits))),
+        }
+    }
+
+    pub fn bit_width(self) -> u32 {
+        match self {
+            NumType::U8 | NumType::I8 => 8,
+            NumType::U16 | NumType::I16 => 16,
+            NumType::U32 | NumType::I32 | NumType::F32 => 32,
+            NumType::U64 | NumType::I64 | NumType::F64 => 64,
+            NumType::U128 | NumType::I128 => 128,
+            // Pointer-sized, so whatever the host target uses.
+            NumType::Usize | NumType::Isize => usize::BITS,
+        }
+    }
+}
+
+impl Statement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Statement::ExprStatement(_) => "ExprStatement",
+            Statement::Let(_) => "Let",
+            Statement::Destructure(_, _) => "Destructure",
+            Statement::Const(_, _, _) => "Const",
+            Statement::Print(_) => "Print",
+            Statement::Block(_) => "Block",
+            Statement::For(_, _, _, _) => "For",
+            Statement::DoWhile(_, _) => "DoWhile",
+        }
+    }
+
+    /// The statements and expressions directly inside this one, in source order. A `let`'s
+    /// declarators without an initializer contribute nothing.
+    pub fn children(&self) -> Vec<Node<'_>> {
+        match self {
+            Statement::ExprStatement(expr)
+            | Statement::Const(_, _, expr)
+            | Statement::Destructure(_, expr) => vec![Node::Expr(expr)],
+            Statement::Let(declarators) => declarators
+                .iter()
+                .filter_map(|(_, _, expr)| expr.as_deref())
+                .map(Node::Expr)
+                .collect(),
+            Statement::Print(args) => args.iter().map(|arg| Node::Expr(arg)).collect(),
+            Statement::Block(body) => body.iter().map(|s| Node::Statement(s)).collect(),
+            Statement::DoWhile(body, cond) => body
+                .iter()
+                .map(|s| Node::Statement(s))
+                .chain([Node::Expr(cond)])
+                .collect(),
+            Statement::For(init, cond, step, body) => init
+                .iter()
+                .map(|s| Node::Statement(s))
+                .chain([cond, step].into_iter().flatten().map(|e| Node::Expr(e)))
+                .chain(body.iter().map(|s| Node::Statement(s)))
+                .collect(),
+        }
+    }
+}
+
+/// Either kind of AST node, for walks that handle statements and expressions alike.
+#[derive(Clone, Copy, Debug)]
+pub enum Node<'a> {
+    Statement(&'a Statement),
+    Expr(&'a Expr),
+}
+
+impl Expr {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Expr::Number(_) => "Number",
+            Expr::Str(_) => "Str",
+            Expr::Bool(_) => "Bool",
+            Expr::Id(_) => "Id",
+            Expr::PI => "PI",
+            Expr::E => "E",
+            Expr::Constant(_) => "Constant",
+            Expr::Call(_, _) => "Call",
+            Expr::Array(_) => "Array",
+            Expr::Tuple(_) => "Tuple",
+            Expr::Index(_, _) => "Index",
+            Expr::Length(_) => "Length",
+            Expr::Method(_, _, _) => "Method",
+            Expr::Template(_) => "Template",
+            Expr::PreIncrement(_) => "PreIncrement",
+            Expr::PreDecrement(_) => "PreDecrement",
+            Expr::PostIncrement(_) => "PostIncrement",
+            Expr::PostDecrement(_) => "PostDecrement",
+            Expr::Parenthesis(_) => "Parenthesis",
+            Expr::Exponent(_, _) => "Exponent",
+            Expr::Multiply(_, _) => "Multiply",
+            Expr::Divide(_, _) => "Divide",
+            Expr::Add(_, _) => "Add",
+            Expr::Sub(_, _) => "Sub",
+            Expr::Cast(_, _) => "Cast",
+            Expr::Saturate(_, _) => "Saturate",
+            Expr::Not(_) => "Not",
+            Expr::Neg(_) => "Neg",
+            Expr::TypeOf(_) => "TypeOf",
+            Expr::BitNot(_) => "BitNot",
+            Expr::Shl(_, _) => "Shl",
+            Expr::Shr(_, _) => "Shr",
+            Expr::Less(_, _) => "Less",
+            Expr::LessEq(_, _) => "LessEq",
+            Expr::Greater(_, _) => "Greater",
+            Expr::GreaterEq(_, _) => "GreaterEq",
+            Expr::Equal(_, _) => "Equal",
+            Expr::NotEqual(_, _) => "NotEqual",
+            Expr::BitAnd(_, _) => "BitAnd",
+            Expr::BitXor(_, _) => "BitXor",
+            Expr::BitOr(_, _) => "BitOr",
+            Expr::And(_, _) => "And",
+            Expr::Or(_, _) => "Or",
+            Expr::Ternary(_, _, _) => "Ternary",
+            Expr::Eq(_, _) => "Eq",
+        }
+    }
+
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Number(_)
+            | Expr::Str(_)
+            | Expr::Bool(_)
+            | Expr::Id(_)
+            | Expr::PI
+            | Expr::E
+            | Expr::Constant(_)
+            | Expr::PreIncrement(_)
+            | Expr::PreDecrement(_)
+            | Expr::PostIncrement(_)
+            | Expr::PostDecrement(_) => vec![],
+            Expr::Call(_, args) | Expr::Array(args) | Expr::Tuple(args) => {
+                args.iter().map(|arg| &**arg).collect()
+            }
+            Expr::Method(receiver, _, args) => std::iter::once(receiver)
+                .chain(args)
+                .map(|arg| &**arg)
+                .collect(),
+            Expr::Template(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    TemplatePart::Expr(expr) => Some(&**expr),
+                    TemplatePart::Str(_) => None,
+                })
+                .collect(),
+            Expr::Parenthesis(expr)
+            | Expr::Length(expr)
+            | Expr::Cast(expr, _)
+            | Expr::Saturate(expr, _)
+            | Expr::Not(expr)
+            | Expr::Neg(expr)
+            | Expr::TypeOf(expr)
+            | Expr::BitNot(expr)
+            | Expr::Eq(_, expr) => vec![expr],
+            Expr::Exponent(l, r)
+            | Expr::Multiply(l, r)
+            | Expr::Divide(l, r)
+            | Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::Shl(l, r)
+            | Expr::Shr(l, r)
+            | Expr::Index(l, r)
+            | Expr::Less(l, r)
+            | Expr::LessEq(l, r)
+            | Expr::Greater(l, r)
+            | Expr::GreaterEq(l, r)
+            | Expr::Equal(l, r)
+            | Expr::NotEqual(l, r)
+            | Expr::BitAnd(l, r)
+            | Expr::BitXor(l, r)
+            | Expr::BitOr(l, r)
+            | Expr::And(l, r)
+            | Expr::Or(l, r) => vec![l, r],
+            Expr::Ternary(c, a, b) => vec![c, a, b],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Box<Expr>> {
+        match self {
+            Expr::Number(_)
+            | Expr::Str(_)
+            | Expr::Bool(_)
+            | Expr::Id(_)
+            | Expr::PI
+            | Expr::E
+            | Expr::Constant(_)
+            | Expr::PreIncrement(_)
+            | Expr::PreDecrement(_)
+            | Expr::PostIncrement(_)
+            | Expr::PostDecrement(_) => vec![],
+            Expr::Call(_, args) | Expr::Array(args) | Expr::Tuple(args) => {
+                args.iter_mut().collect()
+            }
+            Expr::Method(receiver, _, args) => std::iter::once(receiver).chain(args).collect(),
+            Expr::Template(parts) => parts
+                .iter_mut()
+                .filter_map(|part| match part {
+                    TemplatePart::Expr(expr) => Some(expr),
+                    TemplatePart::Str(_) => None,
+                })
+                .collect(),
+            Expr::Parenthesis(expr)
+            | Expr::Length(expr)
+            | Expr::Cast(expr, _)
+            | Expr::Saturate(expr, _)
+            | Expr::Not(expr)
+            | Expr::Neg(expr)
+            | Expr::TypeOf(expr)
+            | Expr::BitNot(expr)
+            | Expr::Eq(_, expr) => vec![expr],
+            Expr::Exponent(l, r)
+            | Expr::Multiply(l, r)
+            | Expr::Divide(l, r)
+            | Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::Shl(l, r)
+            | Expr::Shr(l, r)
+            | Expr::Index(l, r)
+            | Expr::Less(l, r)
+            | Expr::LessEq(l, r)
+            | Expr::Greater(l, r)
+            | Expr::GreaterEq(l, r)
+            | Expr::Equal(l, r)
+            | Expr::NotEqual(l, r)
+            | Expr::BitAnd(l, r)
+            | Expr::BitXor(l, r)
+            | Expr::BitOr(l, r)
+            | Expr::And(l, r)
+            | Expr::Or(l, r) => vec![l, r],
+            Expr::Ternary(c, a, b) => vec![c, a, b],
+        }
+    }
+
+    // Binding strength matching the precedence levels in ts2g.lalrpop (lower binds tighter).
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expr::Number(_)
+            | Expr::Str(_)
+            | Expr::Bool(_)
+            | Expr::Id(_)
+            | Expr::PI
+            | Expr::E
+            | Expr::Constant(_)
+            | Expr::PreIncrement(_)
+            | Expr::PreDecrement(_)
+            | Expr::PostIncrement(_)
+            | Expr::PostDecrement(_)
+            | Expr::Call(_, _)
+            | Expr::Saturate(_, _)
+            | Expr::Array(_)
+            | Expr::Tuple(_)
+            | Expr::Template(_)
+            | Expr::Parenthesis(_) => 0,
+            Expr::Index(_, _) | Expr::Length(_) | Expr::Method(_, _, _) => 1,
+            Expr::Exponent(_, _) => 2,
+            Expr::Not(_) | Expr::Neg(_) | Expr::TypeOf(_) | Expr::BitNot(_) => 3,
+            Expr::Cast(_, _) => 4,
+            Expr::Multiply(_, _) | Expr::Divide(_, _) => 5,
+            Expr::Add(_, _) | Expr::Sub(_, _) => 6,
+            Expr::Shl(_, _) | Expr::Shr(_, _) => 7,
+            Expr::Less(_, _) | Expr::LessEq(_, _) | Expr::Greater(_, _) | Expr::GreaterEq(_, _) => {
+                8
+            }
+            Expr::Equal(_, _) | Expr::NotEqual(_, _) => 9,
+            Expr::BitAnd(_, _) => 10,
+            Expr::BitXor(_, _) => 11,
+            Expr::BitOr(_, _) => 12,
+            Expr::And(_, _) => 13,
+            Expr::Or(_, _) => 14,
+            Expr::Ternary(_, _, _) => 15,
+            Expr::Eq(_, _) => 16,
+        }
+    }
+
+    fn fmt_operand(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        operand: &Expr,
+        tighter: bool,
+    ) -> std::fmt::Result {
+        let needs_parens = if tighter {
+            operand.precedence() >= self.precedence()
+        } else {
+            operand.precedence() > self.precedence()
+        };
+        if needs_parens {
+            write!(f, "({})", operand)
+        } else {
+            write!(f, "{}", operand)
+        }
+    }
+
+    fn fmt_binary(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        l: &Expr,
+        op: &str,
+        r: &Expr,
+        right_assoc: bool,
+    ) -> std::fmt::Result {
+        self.fmt_operand(f, l, right_assoc)?;
+        write!(f, " {} ", op)?;
+        self.fmt_operand(f, r, !right_assoc)
+    }
+}
+fn operand_starts_with_minus(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(Literal::Float(n)) => n.is_sign_negative(),
+        Expr::Neg(_) | Expr::PreDecrement(_) => true,
+        _ => false,
+    }
+}
+// Removes every Parenthesis node below and including `expr`. The tree's shape already encodes
+// precedence, and Display re-inserts the parentheses it needs.
+pub fn strip_parens(expr: &mut Box<Expr>) {
+    while let Expr::Parenthesis(inner) = &mut **expr {
+        *expr = std::mem::replace(inner, Box::new(Expr::Bool(false)));
+    }
+    for child in expr.children_mut() {
+        strip_parens(child);
+    }
+}
+// Resolves the escape sequences allowed in string literals.
+pub fn unescape(s: &str) -> Result<String, &'static str> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('`') => '`',
+            Some('$') => '$',
+            _ => return Err("invalid escape sequence in string literal"),
+        });
+    }
+    Ok(out)
+}
+
+// Builds `l op r` for a relational operator at byte offset `at`. Since they are left-associative,
+// `a < b < c` would otherwise parse as `(a < b) < c` and fail at runtime comparing a bool.
+pub fn comparison(
+    l: Box<Expr>,
+    r: Box<Expr>,
+    at: usize,
+    op: fn(Box<Expr>, Box<Expr>) -> Expr,
+) -> Result<Box<Expr>, (usize, &'static str)> {
+    match *l {
+        Expr::Less(_, _) | Expr::LessEq(_, _) | Expr::Greater(_, _) | Expr::GreaterEq(_, _) => {
+            Err((at, "chained comparison is not allowed; use &&"))
+        }
+        _ => Ok(Box::new(op(l, r))),
+    }
+}
+
+// The value of a whole-number literal's digits, at byte offset `at`. Anything past u128 can't fit
+// any type, so it is rejected while parsing.
+pub fn int_literal(digits: &str, radix: u32, at: usize) -> Result<u128, (usize, &'static str)> {
+    u128::from_str_radix(digits, radix).map_err(|_| (at, "integer literal is too large"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemplatePart {
+    Str(String),
+    Expr(Box<Expr>),
+}
+
+// A piece of a template literal's source, with its byte offset into the text between the
+// backticks.
+pub enum TemplateChunk<'a> {
+    Text(usize, &'a str),
+    Expr(usize, &'a str),
+}
+
+// Splits the inside of a template literal into text and the source of each `${...}`, which the
+// parser then parses as an expression. Braces inside an interpolation nest, and braces inside
+// string literals there don't count. An escaped `\${` is text.
+pub fn split_template(s: &str) -> Result<Vec<TemplateChunk<'_>>, (usize, &'static str)> {
+    let mut chunks = Vec::new();
+    let mut text_start = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '$' if chars.next_if(|&(_, c)| c == '{').is_some() => {
+                chunks.push(TemplateChunk::Text(text_start, &s[text_start..i]));
+                let (mut depth, mut quoted, mut end) = (0, false, None);
+                while let Some((j, c)) = chars.next() {
+                    match c {
+                        '\\' if quoted => {
+                            chars.next();
+                        }
+                        '"' => quoted = !quoted,
+                        '{' if !quoted => depth += 1,
+                        '}' if !quoted && depth == 0 => {
+                            end = Some(j);
+                            break;
+                        }
+                        '}' if !quoted => depth -= 1,
+                        _ => {}
+                    }
+                }
+                let end = end.ok_or((i, "unterminated ${ in template literal"))?;
+                chunks.push(TemplateChunk::Expr(i + 2, &s[i + 2..end]));
+                text_start = end + 1;
+            }
+            _ => {}
+        }
+    }
+    chunks.push(TemplateChunk::Text(text_start, &s[text_start..]));
+    chunks.retain(|chunk| !matches!(chunk, TemplateChunk::Text(_, "")));
+    Ok(chunks)
+}
+
+// Escapes text for the inside of a template literal, the inverse of `unescape` there.
+pub fn escape_template(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '`' => out.push_str("\\`"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Bool(b) => write!(f, "{}", b),
+            Expr::Str(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Expr::Id(id) => write!(f, "{}", id),
+            Expr::PI => write!(f, "PI"),
+            Expr::E => write!(f, "e"),
+            Expr::Constant(c) => write!(f, "{}", c),
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Expr::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                // A one-element tuple keeps its comma, or it would read back as parentheses.
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Expr::Index(array, index) => {
+                self.fmt_operand(f, array, false)?;
+                write!(f, "[{}]", index)
+            }
+            Expr::Length(array) => {
+                self.fmt_operand(f, array, false)?;
+                write!(f, ".length")
+            }
+            Expr::Template(parts) => {
+                write!(f, "`")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Str(s) => write!(f, "{}", escape_template(s))?,
+                        TemplatePart::Expr(expr) => write!(f, "${{{}}}", expr)?,
+                    }
+                }
+                write!(f, "`")
+            }
+            Expr::Method(receiver, name, args) => {
+                self.fmt_operand(f, receiver, false)?;
+                write!(f, ".{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::PreIncrement(id) => write!(f, "++{}", id),
+            Expr::PreDecrement(id) => write!(f, "--{}", id),
+            Expr::PostIncrement(id) => write!(f, "{}++", id),
+            Expr::PostDecrement(id) => write!(f, "{}--", id),
+            Expr::Parenthesis(expr) => write!(f, "({})", expr),
+            Expr::Exponent(l, r) => self.fmt_binary(f, l, "**", r, true),
+            Expr::Multiply(l, r) => self.fmt_binary(f, l, "*", r, false),
+            Expr::Divide(l, r) => self.fmt_binary(f, l, "/", r, false),
+            Expr::Add(l, r) => self.fmt_binary(f, l, "+", r, false),
+            Expr::Sub(l, r) => self.fmt_binary(f, l, "-", r, false),
+            Expr::Saturate(expr, t) => write!(f, "saturate({}, {})", expr, t),
+            Expr::Cast(expr, t) => {
+                self.fmt_operand(f, expr, false)?;
+                write!(f, " as {}", t)
+            }
+            Expr::Not(expr) => {
+                write!(f, "!")?;
+                self.fmt_operand(f, expr, false)
+            }
+            // `- -x` rather than `--x`, which would lex as a decrement.
+            Expr::Neg(expr) => {
+                write!(f, "-")?;
+                if operand_starts_with_minus(expr) {
+                    write!(f, " ")?;
+                }
+                self.fmt_operand(f, expr, false)
+            }
+            Expr::TypeOf(expr) => {
+                write!(f, "typeof ")?;
+                self.fmt_operand(f, expr, false)
+            }
+            Expr::BitNot(expr) => {
+                write!(f, "~")?;
+                self.fmt_operand(f, expr, false)
+            }
+            Expr::Shl(l, r) => self.fmt_binary(f, l, "<<", r, false),
+            Expr::Shr(l, r) => self.fmt_binary(f, l, ">>", r, false),
+            Expr::Less(l, r) => self.fmt_binary(f, l, "<", r, false),
+            Expr::LessEq(l, r) => self.fmt_binary(f, l, "<=", r, false),
+            Expr::Greater(l, r) => self.fmt_binary(f, l, ">", r, false),
+            Expr::GreaterEq(l, r) => self.fmt_binary(f, l, ">=", r, false),
+            Expr::Equal(l, r) => self.fmt_binary(f, l, "==", r, false),
+            Expr::NotEqual(l, r) => self.fmt_binary(f, l, "!=", r, false),
+            Expr::BitAnd(l, r) => self.fmt_binary(f, l, "&", r, false),
+            Expr::BitXor(l, r) => self.fmt_binary(f, l, "^", r, false),
+            Expr::BitOr(l, r) => self.fmt_binary(f, l, "|", r, false),
+            Expr::And(l, r) => self.fmt_binary(f, l, "&&", r, false),
+            Expr::Or(l, r) => self.fmt_binary(f, l, "||", r, false),
+            Expr::Ternary(c, a, b) => {
+                self.fmt_operand(f, c, true)?;
+                write!(f, " ? ")?;
+                self.fmt_operand(f, a, true)?;
+                write!(f, " : ")?;
+                self.fmt_operand(f, b, false)
+            }
+            Expr::Eq(id, expr) => write!(f, "{} = {}", id, expr),
+        }
+    }
+}
+impl std::fmt::Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Statement::ExprStatement(expr) => write!(f, "{};", expr),
+            Statement::Let(declarators) => {
+                write!(f, "let ")?;
+                for (i, (id, t, expr)) in declarators.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", id)?;
+                    if let Some(t) = t {
+                        write!(f, ":{}", t)?;
+                    }
+                    if let Some(expr) = expr {
+                        write!(f, " = {}", expr)?;
+                    }
+                }
+                write!(f, ";")
+            }
+            Statement::Destructure(ids, expr) => write!(f, "let [{}] = {};", ids.join(", "), expr),
+            Statement::Const(id, t, expr) => write!(f, "const {}:{} = {};", id, t, expr),
+            Statement::Print(args) => {
+                write!(f, "print(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ");")
+            }
+            Statement::Block(statements) => {
+                write!(f, "{{")?;
+                for statement in statements {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, " }}")
+            }
+            Statement::For(init, cond, step, body) => {
+                write!(f, "for (")?;
+                match init {
+                    Some(init) => write!(f, "{}", init)?,
+                    None => write!(f, ";")?,
+                }
+                if let Some(cond) = cond {
+                    write!(f, " {}", cond)?;
+                }
+                write!(f, ";")?;
+                if let Some(step) = step {
+                    write!(f, " {}", step)?;
+                }
+                write!(f, ") {{")?;
+                for statement in body {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, " }}")
+            }
+            Statement::DoWhile(body, cond) => {
+                write!(f, "do {{")?;
+                for statement in body {
+                    write!(f, " {}", statement)?;
+                }
+                write!(f, " }} while ({});", cond)
+            }
+        }
+    }
 }
\ No newline at end of file
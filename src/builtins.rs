@@ -0,0 +1,528 @@
+use crate::{InterpError, NumType, Number, Object, Value};
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// SplitMix64: tiny, fast, and good enough for procedural generation. Any seed is valid.
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // Seeded from the clock, so every run differs unless a seed is given.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self::new(nanos)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1): the top 53 bits fill an f64 mantissa exactly.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// None for functions that return nothing, which may only be called as a statement.
+pub fn call(name: &str, args: Vec<Object>, rng: &mut Rng) -> Result<Option<Object>, InterpError> {
+    let v = match name {
+        "Math.random" => {
+            if !args.is_empty() {
+                return Err(InterpError::InvalidArgument(format!(
+                    "{} expects no arguments but got {}.",
+                    name,
+                    args.len()
+                )));
+            }
+            Value {
+                t: NumType::F64,
+                v: Number {
+                    f64: rng.next_f64(),
+                },
+            }
+        }
+        // Integers convert to finite, non-NaN f64s, so these are constant for them.
+        "assert" => return assert(name, args).map(|()| None),
+        "isNaN" => return Ok(Some(Object::Bool(as_f64(unary(name, args)?).is_nan()))),
+        "isFinite" => return Ok(Some(Object::Bool(as_f64(unary(name, args)?).is_finite()))),
+        "sqrt" => sqrt(unary(name, args)?)?,
+        "abs" => abs(unary(name, args)?)?,
+        "floor" => map_float(unary(name, args)?, f32::floor, f64::floor),
+        "ceil" => map_float(unary(name, args)?, f32::ceil, f64::ceil),
+        "round" => map_float(unary(name, args)?, f32::round, f64::round),
+        "Math.sin" => map_f64(unary(name, args)?, f64::sin),
+        "Math.cos" => map_f64(unary(name, args)?, f64::cos),
+        "Math.tan" => map_f64(unary(name, args)?, f64::tan),
+        "Math.log" => map_f64(unary(name, args)?, f64::ln),
+        "Math.log2" => map_f64(unary(name, args)?, f64::log2),
+        "Math.log10" => map_f64(unary(name, args)?, f64::log10),
+        "Math.exp" => map_f64(unary(name, args)?, f64::exp),
+        "Math.trunc" => map_f64(unary(name, args)?, f64::trunc),
+        "Math.cbrt" => map_f64(unary(name, args)?, f64::cbrt),
+        "Math.sign" => sign(unary(name, args)?),
+        "min" => extreme(name, args, Ordering::Less)?,
+        "max" => extreme(name, args, Ordering::Greater)?,
+        "clamp" => clamp(name, args)?,
+        "parseInt" => parse_int(name, args)?,
+        "parseFloat" => parse_float(name, args)?,
+        "includes" => return Ok(Some(Object::Bool(includes(name, args)?))),
+        _ => return Err(InterpError::UnknownFunction(name.to_owned())),
+    };
+    Ok(Some(Object::Number(v)))
+}
+
+fn unary(name: &str, mut args: Vec<Object>) -> Result<Value, InterpError> {
+    if args.len() != 1 {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects 1 argument but got {}.",
+            name,
+            args.len()
+        )));
+    }
+    match args.pop().unwrap() {
+        Object::Number(v) => Ok(v),
+        o => Err(InterpError::InvalidArgument(format!(
+            "{} expects a number but got {}.",
+            name,
+            o.type_name()
+        ))),
+    }
+}
+
+// Methods called on a value, e.g. `s.toUpperCase()`. Only strings have any so far.
+pub fn method(receiver: Object, name: &str, args: Vec<Object>) -> Result<Object, InterpError> {
+    let Object::Str(s) = receiver else {
+        return Err(InterpError::UnknownFunction(format!(
+            "{}.{}",
+            receiver.type_name(),
+            name
+        )));
+    };
+    let s = match name {
+        "toUpperCase" | "toLowerCase" if !args.is_empty() => {
+            return Err(InterpError::InvalidArgument(format!(
+                "{} expects no arguments but got {}.",
+                name,
+                args.len()
+            )));
+        }
+        "toUpperCase" => s.to_uppercase(),
+        "toLowerCase" => s.to_lowercase(),
+        "substring" => substring(name, &s, args)?,
+        _ => return Err(InterpError::UnknownFunction(format!("string.{}", name))),
+    };
+    Ok(Object::Str(s))
+}
+
+// Like JS: indices count chars, are clamped to [0, length] rather than erroring, and are swapped
+// if the start is past the end. Without an end the rest of the string is taken.
+fn substring(name: &str, s: &str, args: Vec<Object>) -> Result<String, InterpError> {
+    let len = s.chars().count();
+    let index = |arg: Object| match arg {
+        Object::Number(v) => {
+            let n = as_f64(v);
+            Ok(if n.is_nan() {
+                0
+            } else {
+                n.clamp(0.0, len as f64) as usize
+            })
+        }
+        o => Err(InterpError::InvalidArgument(format!(
+            "{} expects a number but got {}.",
+            name,
+            o.type_name()
+        ))),
+    };
+    let mut args = args.into_iter();
+    let (Some(start), end, None) = (args.next(), args.next(), args.next()) else {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects 1 or 2 arguments.",
+            name
+        )));
+    };
+    let start = index(start)?;
+    let end = end.map(index).transpose()?.unwrap_or(len);
+    let (start, end) = (start.min(end), start.max(end));
+    Ok(s.chars().skip(start).take(end - start).collect())
+}
+
+fn assert(name: &str, args: Vec<Object>) -> Result<(), InterpError> {
+    let mut args = args.into_iter();
+    let (Some(cond), message, None) = (args.next(), args.next(), args.next()) else {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects 1 or 2 arguments.",
+            name
+        )));
+    };
+    let message = message.map(|m| string(name, m)).transpose()?;
+    match cond {
+        Object::Bool(true) => Ok(()),
+        Object::Bool(false) => Err(InterpError::AssertionFailed(message)),
+        o => Err(InterpError::InvalidArgument(format!(
+            "{} expects a bool but got {}.",
+            name,
+            o.type_name()
+        ))),
+    }
+}
+
+fn string(name: &str, arg: Object) -> Result<String, InterpError> {
+    match arg {
+        Object::Str(s) => Ok(s),
+        o => Err(InterpError::InvalidArgument(format!(
+            "{} expects a string but got {}.",
+            name,
+            o.type_name()
+        ))),
+    }
+}
+
+// Like JS, leading whitespace and trailing junk are ignored ("42px" is 42). Unlike JS there is
+// no integer NaN, so a string without any digits is an error rather than NaN.
+fn parse_int(name: &str, args: Vec<Object>) -> Result<Value, InterpError> {
+    let mut args = args.into_iter();
+    let (Some(s), radix, None) = (args.next(), args.next(), args.next()) else {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects 1 or 2 arguments.",
+            name
+        )));
+    };
+    let s = string(name, s)?;
+    let radix = match radix {
+        None => 10,
+        Some(radix) => {
+            let radix = as_f64(unary(name, vec![radix])?);
+            if radix.fract() != 0.0 || !(2.0..=36.0).contains(&radix) {
+                return Err(InterpError::InvalidArgument(format!(
+                    "{} radix must be an integer between 2 and 36, got {}.",
+                    name, radix
+                )));
+            }
+            radix as u32
+        }
+    };
+    let trimmed = s.trim_start();
+    let unsigned = trimmed.trim_start_matches(['+', '-']);
+    let sign_len = trimmed.len() - unsigned.len();
+    let digits = unsigned
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(unsigned.len());
+    if sign_len > 1 || digits == 0 {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} could not parse {:?}.",
+            name, s
+        )));
+    }
+    i64::from_str_radix(&trimmed[..sign_len + digits], radix)
+        .map(|n| Value::from_i128(n as i128, NumType::I64))
+        .map_err(|_| InterpError::InvalidArgument(format!("{} of {:?} overflows i64.", name, s)))
+}
+
+// Unparseable strings give NaN, as in JS, since f64 can represent it.
+fn parse_float(name: &str, args: Vec<Object>) -> Result<Value, InterpError> {
+    if args.len() != 1 {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects 1 argument but got {}.",
+            name,
+            args.len()
+        )));
+    }
+    let s = string(name, args.into_iter().next().unwrap())?;
+    let n = s.trim().parse().unwrap_or(f64::NAN);
+    Ok(Value {
+        t: NumType::F64,
+        v: Number { f64: n },
+    })
+}
+
+// Arguments of the multi-argument built-ins must all share one NumType, so they are compared
+// natively rather than through f64 (which can't tell large u64s apart). Bare literals have
+// already taken the other arguments' type by the time they get here.
+fn numbers(name: &str, args: Vec<Object>) -> Result<Vec<Value>, InterpError> {
+    let values = args
+        .into_iter()
+        .map(|arg| match arg {
+            Object::Number(v) => Ok(v),
+            o => Err(InterpError::InvalidArgument(format!(
+                "{} expects numbers but got {}.",
+                name,
+                o.type_name()
+            ))),
+        })
+        .collect::<Result<Vec<Value>, InterpError>>()?;
+    if let Some(first) = values.first()
+        && let Some(other) = values.iter().find(|v| v.t != first.t)
+    {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects arguments of one type but got {} and {}.",
+            name, first.t, other.t
+        )));
+    }
+    Ok(values)
+}
+
+fn extreme(name: &str, args: Vec<Object>, keep: Ordering) -> Result<Value, InterpError> {
+    numbers(name, args)?
+        .into_iter()
+        .reduce(|a, b| {
+            if b.partial_cmp(&a) == Some(keep) {
+                b
+            } else {
+                a
+            }
+        })
+        .ok_or_else(|| {
+            InterpError::InvalidArgument(format!("{} expects at least 1 argument.", name))
+        })
+}
+
+fn clamp(name: &str, args: Vec<Object>) -> Result<Value, InterpError> {
+    let values = numbers(name, args)?;
+    let [x, lo, hi] = values[..] else {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects 3 arguments but got {}.",
+            name,
+            values.len()
+        )));
+    };
+    if lo > hi {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} lower bound {} is greater than upper bound {}.",
+            name, lo, hi
+        )));
+    }
+    Ok(if x < lo {
+        lo
+    } else if x > hi {
+        hi
+    } else {
+        x
+    })
+}
+
+// Compares with Value's own equality, so NaN is never found. The value must have the elements'
+// type; an empty array has none to check against, so it simply doesn't contain anything.
+fn includes(name: &str, args: Vec<Object>) -> Result<bool, InterpError> {
+    let [Object::Array(values), Object::Number(v)] = &args[..] else {
+        return Err(InterpError::InvalidArgument(format!(
+            "{} expects an array and a number.",
+            name
+        )));
+    };
+    if let Some(first) = values.first()
+        && first.t != v.t
+    {
+        return Err(InterpError::TypeMismatch(
+            first.t.to_string(),
+            v.t.to_string(),
+        ));
+    }
+    Ok(values.contains(v))
+}
+
+fn as_f64(v: Value) -> f64 {
+    unsafe { v.cast(NumType::F64).v.f64 }
+}
+
+// The Math.* functions work in f64 whatever the operand type.
+fn map_f64(v: Value, f: fn(f64) -> f64) -> Value {
+    Value {
+        t: NumType::F64,
+        v: Number { f64: f(as_f64(v)) },
+    }
+}
+
+// Integers are already whole, so rounding functions only touch floats.
+fn map_float(v: Value, f32: fn(f32) -> f32, f64: fn(f64) -> f64) -> Value {
+    match v.t {
+        NumType::F32 => Value {
+            t: v.t,
+            v: Number {
+                f32: f32(unsafe { v.v.f32 }),
+            },
+        },
+        NumType::F64 => Value {
+            t: v.t,
+            v: Number {
+                f64: f64(unsafe { v.v.f64 }),
+            },
+        },
+        _ => v,
+    }
+}
+
+// -1, 0 or 1 in the operand's own type. As in JS, NaN and both zeros are returned unchanged
+// rather than taking Rust's signum, which maps 0.0 to 1.0.
+fn sign(v: Value) -> Value {
+    if v.is_float() {
+        return map_float(
+            v,
+            |x| {
+                if x == 0.0 || x.is_nan() {
+                    x
+                } else {
+                    x.signum()
+                }
+            },
+            |x| {
+                if x == 0.0 || x.is_nan() {
+                    x
+                } else {
+                    x.signum()
+                }
+            },
+        );
+    }
+    match v.partial_cmp(&Value::zero(v.t)) {
+        Some(Ordering::Less) => Value::from_i128(-1, v.t),
+        Some(Ordering::Greater) => Value::one(v.t),
+        _ => Value::zero(v.t),
+    }
+}
+
+// The most negative signed value has no positive counterpart in its type.
+fn abs(v: Value) -> Result<Value, InterpError> {
+    let overflow = || InterpError::InvalidArgument(format!("abs of {} overflows {}.", v, v.t));
+    Ok(match v.t {
+        NumType::I8 => Value {
+            t: v.t,
+            v: Number {
+                i8: unsafe { v.v.i8 }.checked_abs().ok_or_else(overflow)?,
+            },
+        },
+        NumType::I16 => Value {
+            t: v.t,
+            v: Number {
+                i16: unsafe { v.v.i16 }.checked_abs().ok_or_else(overflow)?,
+            },
+        },
+        NumType::I32 => Value {
+            t: v.t,
+            v: Number {
+                i32: unsafe { v.v.i32 }.checked_abs().ok_or_else(overflow)?,
+            },
+        },
+        NumType::I64 => Value {
+            t: v.t,
+            v: Number {
+                i64: unsafe { v.v.i64 }.checked_abs().ok_or_else(overflow)?,
+            },
+        },
+        NumType::Isize => Value {
+            t: v.t,
+            v: Number {
+                isize: unsafe { v.v.isize }.checked_abs().ok_or_else(overflow)?,
+            },
+        },
+        NumType::I128 => Value {
+            t: v.t,
+            v: Number {
+                i128: unsafe { v.v.i128 }.checked_abs().ok_or_else(overflow)?,
+            },
+        },
+        NumType::F32 | NumType::F64 => map_float(v, f32::abs, f64::abs),
+        NumType::U8
+        | NumType::U16
+        | NumType::U32
+        | NumType::U64
+        | NumType::Usize
+        | NumType::U128 => v,
+    })
+}
+
+// Integer square roots round down and keep the operand's type.
+fn sqrt(v: Value) -> Result<Value, InterpError> {
+    if v.is_integer() && v < Value::zero(v.t) {
+        return Err(InterpError::InvalidArgument(format!(
+            "sqrt of negative integer {}.",
+            v
+        )));
+    }
+    Ok(match v.t {
+        NumType::U8 => Value {
+            t: v.t,
+            v: Number {
+                u8: unsafe { v.v.u8 }.isqrt(),
+            },
+        },
+        NumType::I8 => Value {
+            t: v.t,
+            v: Number {
+                i8: unsafe { v.v.i8 }.isqrt(),
+            },
+        },
+        NumType::U16 => Value {
+            t: v.t,
+            v: Number {
+                u16: unsafe { v.v.u16 }.isqrt(),
+            },
+        },
+        NumType::I16 => Value {
+            t: v.t,
+            v: Number {
+                i16: unsafe { v.v.i16 }.isqrt(),
+            },
+        },
+        NumType::U32 => Value {
+            t: v.t,
+            v: Number {
+                u32: unsafe { v.v.u32 }.isqrt(),
+            },
+        },
+        NumType::I32 => Value {
+            t: v.t,
+            v: Number {
+                i32: unsafe { v.v.i32 }.isqrt(),
+            },
+        },
+        NumType::U64 => Value {
+            t: v.t,
+            v: Number {
+                u64: unsafe { v.v.u64 }.isqrt(),
+            },
+        },
+        NumType::I64 => Value {
+            t: v.t,
+            v: Number {
+                i64: unsafe { v.v.i64 }.isqrt(),
+            },
+        },
+        NumType::Usize => Value {
+            t: v.t,
+            v: Number {
+                usize: unsafe { v.v.usize }.isqrt(),
+            },
+        },
+        NumType::Isize => Value {
+            t: v.t,
+            v: Number {
+                isize: unsafe { v.v.isize }.isqrt(),
+            },
+        },
+        NumType::U128 => Value {
+            t: v.t,
+            v: Number {
+                u128: unsafe { v.v.u128 }.isqrt(),
+            },
+        },
+        NumType::I128 => Value {
+            t: v.t,
+            v: Number {
+                i128: unsafe { v.v.i128 }.isqrt(),
+            },
+        },
+        NumType::F32 | NumType::F64 => map_float(v, f32::sqrt, f64::sqrt),
+    })
+}
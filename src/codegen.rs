@@ -0,0 +1,966 @@
+use crate::ast::{Constant, Expr, Literal, NumType, Spanned, Statement, TemplatePart, escape_template};
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+#[derive(Debug)]
+pub struct CodegenError {
+    pub msg: String,
+}
+impl CodegenError {
+    fn new(msg: impl Into<String>) -> Self {
+        Self { msg: msg.into() }
+    }
+}
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "codegen error: {}", self.msg)
+    }
+}
+impl Error for CodegenError {}
+
+// GLSL has no 8/16-bit or 64-bit integers without extensions; doubles need GLSL 4.00.
+fn glsl_type(t: &str) -> Result<&'static str, CodegenError> {
+    match t {
+        "bool" => Ok("bool"),
+        "u32" => Ok("uint"),
+        "i32" => Ok("int"),
+        "f32" => Ok("float"),
+        "f64" => Ok("double"),
+        _ => Err(CodegenError::new(format!(
+            "type {} has no GLSL equivalent",
+            t
+        ))),
+    }
+}
+
+struct GlslWriter {
+    out: String,
+    indent: usize,
+    // GLSL type of every variable in scope, innermost scope last.
+    scopes: Vec<HashMap<String, &'static str>>,
+}
+impl GlslWriter {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn lookup(&self, id: &str) -> Result<&'static str, CodegenError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(id).copied())
+            .ok_or_else(|| CodegenError::new(format!("variable {} is not declared", id)))
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), CodegenError> {
+        match statement {
+            Statement::Block(statements) => {
+                self.line("{");
+                self.block(statements)?;
+                self.line("}");
+            }
+            Statement::For(init, cond, step, body) => {
+                self.scopes.push(HashMap::new());
+                let init = match init.as_deref() {
+                    Some(init) => self.simple(init)?,
+                    None => ";".to_owned(),
+                };
+                if init.matches(';').count() > 1 {
+                    return Err(CodegenError::new(
+                        "for loop declarations must share one type in GLSL",
+                    ));
+                }
+                let cond = match cond {
+                    Some(cond) => format!(" {}", self.expr(cond, None)?),
+                    None => String::new(),
+                };
+                let step = match step {
+                    Some(step) => format!(" {}", self.expr(step, None)?),
+                    None => String::new(),
+                };
+                self.line(&format!("for ({}{};{}) {{", init, cond, step));
+                self.block(body)?;
+                self.line("}");
+                self.scopes.pop();
+            }
+            Statement::DoWhile(body, cond) => {
+                self.line("do {");
+                self.block(body)?;
+                let cond = self.expr(cond, None)?;
+                self.line(&format!("}} while ({});", cond));
+            }
+            _ => {
+                let line = self.simple(statement)?;
+                self.line(&line);
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &[Box<Spanned<Statement>>]) -> Result<(), CodegenError> {
+        self.scopes.push(HashMap::new());
+        self.indent += 1;
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    // Single-line statements, which can also serve as a for loop's init clause.
+    fn simple(&mut self, statement: &Statement) -> Result<String, CodegenError> {
+        match statement {
+            Statement::ExprStatement(expr) => Ok(format!("{};", self.expr(expr, None)?)),
+            // GLSL can only declare several variables at once when they share a type, so mixed
+            // declarators become separate declarations.
+            Statement::Let(declarators) => {
+                let mut lines: Vec<(&'static str, Vec<String>)> = Vec::new();
+                for (id, t, expr) in declarators {
+                    let t = match (t, expr) {
+                        (Some(t), _) => glsl_type(t)?,
+                        // Untyped literals are f64s, as in the interpreter.
+                        (None, Some(expr)) => self.infer(expr)?.unwrap_or("double"),
+                        (None, None) => {
+                            return Err(CodegenError::new(format!(
+                                "{} needs a type or an initializer in GLSL",
+                                id
+                            )));
+                        }
+                    };
+                    let declarator = match expr {
+                        Some(expr) => format!("{} = {}", id, self.expr(expr, Some(t))?),
+                        None => id.clone(),
+                    };
+                    self.scopes.last_mut().unwrap().insert(id.clone(), t);
+                    match lines.last_mut() {
+                        Some((last, declarators)) if *last == t => declarators.push(declarator),
+                        _ => lines.push((t, vec![declarator])),
+                    }
+                }
+                Ok(lines
+                    .into_iter()
+                    .map(|(t, declarators)| format!("{} {};", t, declarators.join(", ")))
+                    .collect::<Vec<_>>()
+                    .join(" "))
+            }
+            Statement::Const(id, t, expr) => {
+                let t = glsl_type(t)?;
+                let line = format!("const {} {} = {};", t, id, self.expr(expr, Some(t))?);
+                self.scopes.last_mut().unwrap().insert(id.clone(), t);
+                Ok(line)
+            }
+            Statement::Print(_) => Err(CodegenError::new("print has no GLSL equivalent")),
+            Statement::Destructure(_, _) => Err(CodegenError::new(
+                "array destructuring has no GLSL equivalent",
+            )),
+            Statement::Block(_) | Statement::For(_, _, _, _) | Statement::DoWhile(_, _) => {
+                Err(CodegenError::new(format!(
+                    "{} cannot be used as a for loop initializer",
+                    statement.name()
+                )))
+            }
+        }
+    }
+
+    // The GLSL type an expression has on its own, or None for bare literals, which adapt to
+    // whatever they are combined with.
+    fn infer(&self, expr: &Expr) -> Result<Option<&'static str>, CodegenError> {
+        Ok(match expr {
+            Expr::Number(_) | Expr::PI | Expr::E => None,
+            Expr::Constant(Constant::Infinity | Constant::NaN) => {
+                return Err(CodegenError::new(format!("{} has no GLSL literal", expr)));
+            }
+            Expr::Constant(_) => None,
+            Expr::Bool(_) => Some("bool"),
+            Expr::Id(id)
+            | Expr::PreIncrement(id)
+            | Expr::PreDecrement(id)
+            | Expr::PostIncrement(id)
+            | Expr::PostDecrement(id)
+            | Expr::Eq(id, _) => Some(self.lookup(id)?),
+            Expr::Parenthesis(expr)
+            | Expr::Neg(expr)
+            | Expr::BitNot(expr)
+            | Expr::Shl(expr, _)
+            | Expr::Shr(expr, _) => self.infer(expr)?,
+            Expr::Cast(_, t) | Expr::Saturate(_, t) => Some(glsl_type(&t.to_string())?),
+            Expr::Exponent(l, r)
+            | Expr::Multiply(l, r)
+            | Expr::Divide(l, r)
+            | Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::BitAnd(l, r)
+            | Expr::BitXor(l, r)
+            | Expr::BitOr(l, r)
+            | Expr::Ternary(_, l, r) => self.infer(l)?.or(self.infer(r)?),
+            Expr::Not(_)
+            | Expr::Less(_, _)
+            | Expr::LessEq(_, _)
+            | Expr::Greater(_, _)
+            | Expr::GreaterEq(_, _)
+            | Expr::Equal(_, _)
+            | Expr::NotEqual(_, _)
+            | Expr::And(_, _)
+            | Expr::Or(_, _) => Some("bool"),
+            Expr::Call(name, _) if name.starts_with("Math.") && name != "Math.sign" => {
+                Some("float")
+            }
+            Expr::Call(_, args) => {
+                let mut t = None;
+                for arg in args {
+                    t = t.or(self.infer(arg)?);
+                }
+                t
+            }
+            Expr::Str(_)
+            | Expr::TypeOf(_)
+            | Expr::Array(_)
+            | Expr::Tuple(_)
+            | Expr::Index(_, _)
+            | Expr::Length(_)
+            | Expr::Method(_, _, _)
+            | Expr::Template(_) => {
+                return Err(CodegenError::new(format!(
+                    "{} has no GLSL equivalent",
+                    expr.name()
+                )));
+            }
+        })
+    }
+
+    fn literal(n: f64, t: Option<&'static str>) -> Result<String, CodegenError> {
+        let whole = n.fract() == 0.0;
+        match t.unwrap_or("double") {
+            "uint" if whole && (0.0..=u32::MAX as f64).contains(&n) => Ok(format!("{}u", n)),
+            "int" if whole && (i32::MIN as f64..=i32::MAX as f64).contains(&n) => {
+                Ok(format!("{}", n))
+            }
+            "float" => Ok(format!("{:?}", n)),
+            "double" => Ok(format!("{:?}lf", n)),
+            t => Err(CodegenError::new(format!(
+                "literal {} is not a valid {}",
+                n, t
+            ))),
+        }
+    }
+
+    // Casts, exponents and calls all become function calls in GLSL, so they bind tightest.
+    fn precedence(expr: &Expr) -> u8 {
+        match expr {
+            Expr::Cast(_, _) | Expr::Exponent(_, _) => 0,
+            _ => expr.precedence(),
+        }
+    }
+
+    fn operand(
+        &mut self,
+        parent: &Expr,
+        operand: &Expr,
+        tighter: bool,
+        t: Option<&'static str>,
+    ) -> Result<String, CodegenError> {
+        let code = self.expr(operand, t)?;
+        let (parent, operand) = (Self::precedence(parent), Self::precedence(operand));
+        Ok(
+            if operand > parent || (tighter && operand == parent && operand > 0) {
+                format!("({})", code)
+            } else {
+                code
+            },
+        )
+    }
+
+    fn binary(
+        &mut self,
+        parent: &Expr,
+        l: &Expr,
+        op: &str,
+        r: &Expr,
+        t: Option<&'static str>,
+    ) -> Result<String, CodegenError> {
+        Ok(format!(
+            "{} {} {}",
+            self.operand(parent, l, false, t)?,
+            op,
+            self.operand(parent, r, true, t)?
+        ))
+    }
+
+    // GLSL's transcendental built-ins only take floats.
+    fn float_arg(&mut self, arg: &Expr) -> Result<String, CodegenError> {
+        let code = self.expr(arg, Some("float"))?;
+        Ok(match self.infer(arg)? {
+            Some(t) if t != "float" => format!("float({})", code),
+            _ => code,
+        })
+    }
+
+    // `hint` is the type the surrounding code expects, which bare literals adopt.
+    fn expr(&mut self, expr: &Expr, hint: Option<&'static str>) -> Result<String, CodegenError> {
+        let t = self.infer(expr)?.or(hint);
+        Ok(match expr {
+            Expr::Number(n) => Self::literal(n.as_f64(), t)?,
+            Expr::PI => Self::literal(std::f64::consts::PI, t.or(Some("float")))?,
+            Expr::E => Self::literal(std::f64::consts::E, t.or(Some("float")))?,
+            Expr::Constant(c) => Self::literal(c.value(), t.or(Some("float")))?,
+            Expr::Bool(b) => b.to_string(),
+            Expr::Id(id) => id.clone(),
+            Expr::PreIncrement(id) => format!("++{}", id),
+            Expr::PreDecrement(id) => format!("--{}", id),
+            Expr::PostIncrement(id) => format!("{}++", id),
+            Expr::PostDecrement(id) => format!("{}--", id),
+            Expr::Parenthesis(inner) => format!("({})", self.expr(inner, t)?),
+            Expr::Exponent(l, r) => {
+                let pow = format!("pow({}, {})", self.float_arg(l)?, self.float_arg(r)?);
+                match t {
+                    Some("float") => pow,
+                    Some(t) => format!("{}({})", t, pow),
+                    None => format!("double({})", pow),
+                }
+            }
+            Expr::Cast(inner, t) => {
+                format!(
+                    "{}({})",
+                    glsl_type(&t.to_string())?,
+                    self.expr(inner, None)?
+                )
+            }
+            Expr::Saturate(_, _) => {
+                return Err(CodegenError::new("saturate has no GLSL equivalent"));
+            }
+            Expr::Not(inner) | Expr::Neg(inner) | Expr::BitNot(inner) => {
+                let op = match expr {
+                    Expr::Not(_) => "!",
+                    Expr::Neg(_) => "-",
+                    _ => "~",
+                };
+                let code = self.operand(expr, inner, false, t)?;
+                // `- -x` rather than `--x`, which is a decrement.
+                let space = if op == "-" && code.starts_with('-') {
+                    " "
+                } else {
+                    ""
+                };
+                format!("{}{}{}", op, space, code)
+            }
+            Expr::Multiply(l, r) => self.binary(expr, l, "*", r, t)?,
+            Expr::Divide(l, r) => self.binary(expr, l, "/", r, t)?,
+            Expr::Add(l, r) => self.binary(expr, l, "+", r, t)?,
+            Expr::Sub(l, r) => self.binary(expr, l, "-", r, t)?,
+            Expr::BitAnd(l, r) => self.binary(expr, l, "&", r, t)?,
+            Expr::BitXor(l, r) => self.binary(expr, l, "^", r, t)?,
+            Expr::BitOr(l, r) => self.binary(expr, l, "|", r, t)?,
+            Expr::Shl(l, r) | Expr::Shr(l, r) => {
+                let op = if matches!(expr, Expr::Shl(_, _)) {
+                    "<<"
+                } else {
+                    ">>"
+                };
+                let amount = self.infer(r)?.or(Some("uint"));
+                format!(
+                    "{} {} {}",
+                    self.operand(expr, l, false, t)?,
+                    op,
+                    self.operand(expr, r, true, amount)?
+                )
+            }
+            Expr::Less(l, r)
+            | Expr::LessEq(l, r)
+            | Expr::Greater(l, r)
+            | Expr::GreaterEq(l, r)
+            | Expr::Equal(l, r)
+            | Expr::NotEqual(l, r) => {
+                let op = match expr {
+                    Expr::Less(_, _) => "<",
+                    Expr::LessEq(_, _) => "<=",
+                    Expr::Greater(_, _) => ">",
+                    Expr::GreaterEq(_, _) => ">=",
+                    Expr::Equal(_, _) => "==",
+                    _ => "!=",
+                };
+                let operands = self.infer(l)?.or(self.infer(r)?);
+                self.binary(expr, l, op, r, operands)?
+            }
+            Expr::And(l, r) => self.binary(expr, l, "&&", r, None)?,
+            Expr::Or(l, r) => self.binary(expr, l, "||", r, None)?,
+            Expr::Ternary(c, a, b) => format!(
+                "{} ? {} : {}",
+                self.operand(expr, c, true, None)?,
+                self.operand(expr, a, true, t)?,
+                self.operand(expr, b, false, t)?
+            ),
+            Expr::Eq(id, value) => {
+                let t = self.lookup(id)?;
+                format!("{} = {}", id, self.expr(value, Some(t))?)
+            }
+            Expr::Call(name, args) => self.call(name, args, t)?,
+            Expr::Str(_)
+            | Expr::TypeOf(_)
+            | Expr::Array(_)
+            | Expr::Tuple(_)
+            | Expr::Index(_, _)
+            | Expr::Length(_)
+            | Expr::Method(_, _, _)
+            | Expr::Template(_) => {
+                unreachable!("rejected by infer")
+            }
+        })
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Box<Expr>],
+        t: Option<&'static str>,
+    ) -> Result<String, CodegenError> {
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(CodegenError::new(format!(
+                    "{} expects {} arguments but got {}",
+                    name,
+                    n,
+                    args.len()
+                )))
+            }
+        };
+        Ok(match name {
+            "sqrt" | "abs" | "floor" | "ceil" | "round" => {
+                arity(1)?;
+                format!("{}({})", name, self.expr(&args[0], t)?)
+            }
+            "Math.sin" | "Math.cos" | "Math.tan" | "Math.log" | "Math.log2" | "Math.exp"
+            | "Math.trunc" => {
+                arity(1)?;
+                format!("{}({})", &name[5..], self.float_arg(&args[0])?)
+            }
+            "Math.log10" => {
+                arity(1)?;
+                format!("(log({}) / log(10.0))", self.float_arg(&args[0])?)
+            }
+            // GLSL's sign has no unsigned overload; for uint it is just min(x, 1u).
+            "Math.sign" => {
+                arity(1)?;
+                let code = self.expr(&args[0], t)?;
+                match self.infer(&args[0])?.or(t) {
+                    Some("uint") => format!("min({}, 1u)", code),
+                    _ => format!("sign({})", code),
+                }
+            }
+            // GLSL's min and max take two arguments, so longer lists nest.
+            "min" | "max" => {
+                let mut args = args.iter().rev();
+                let last = args
+                    .next()
+                    .ok_or_else(|| CodegenError::new(format!("{} expects arguments", name)))?;
+                let mut code = self.expr(last, t)?;
+                for arg in args {
+                    code = format!("{}({}, {})", name, self.expr(arg, t)?, code);
+                }
+                code
+            }
+            "clamp" => {
+                arity(3)?;
+                format!(
+                    "clamp({}, {}, {})",
+                    self.expr(&args[0], t)?,
+                    self.expr(&args[1], t)?,
+                    self.expr(&args[2], t)?
+                )
+            }
+            _ => {
+                return Err(CodegenError::new(format!(
+                    "function {} has no GLSL equivalent",
+                    name
+                )));
+            }
+        })
+    }
+}
+
+// Emits the program as the body of a GLSL compute-style `main`.
+pub fn to_glsl(stmts: &[Box<Spanned<Statement>>]) -> Result<String, CodegenError> {
+    let mut writer = GlslWriter {
+        out: String::from("#version 450\n\nvoid main() {\n"),
+        indent: 0,
+        scopes: Vec::new(),
+    };
+    writer.block(stmts)?;
+    writer.out.push_str("}\n");
+    Ok(writer.out)
+}
+
+struct JsWriter {
+    out: String,
+    indent: usize,
+    // Type of every variable in scope, innermost scope last; None for anything but an integer.
+    // JS only has doubles, so integer division and wraparound have to be written out.
+    scopes: Vec<HashMap<String, Option<NumType>>>,
+}
+impl JsWriter {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Block(statements) => {
+                self.line("{");
+                self.block(statements);
+                self.line("}");
+            }
+            Statement::For(init, cond, step, body) => {
+                self.scopes.push(HashMap::new());
+                let init = match init.as_deref() {
+                    Some(init) => self.simple(init),
+                    None => ";".to_owned(),
+                };
+                let cond = cond
+                    .as_ref()
+                    .map(|cond| format!(" {}", self.expr(cond)))
+                    .unwrap_or_default();
+                let step = step
+                    .as_ref()
+                    .map(|step| format!(" {}", self.discarded(step)))
+                    .unwrap_or_default();
+                self.line(&format!("for ({}{};{}) {{", init, cond, step));
+                self.block(body);
+                self.line("}");
+                self.scopes.pop();
+            }
+            Statement::DoWhile(body, cond) => {
+                self.line("do {");
+                self.block(body);
+                self.line(&format!("}} while ({});", self.expr(cond)));
+            }
+            _ => {
+                let line = self.simple(statement);
+                self.line(&line);
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Box<Spanned<Statement>>]) {
+        self.scopes.push(HashMap::new());
+        self.indent += 1;
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, id: &str, t: Option<NumType>) {
+        let t = t.filter(|t| t.is_integer());
+        self.scopes.last_mut().unwrap().insert(id.to_owned(), t);
+    }
+
+    fn lookup(&self, id: &str) -> Option<NumType> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(id))
+            .copied()
+            .flatten()
+    }
+
+    fn simple(&mut self, statement: &Statement) -> String {
+        match statement {
+            Statement::ExprStatement(expr) => format!("{};", self.discarded(expr)),
+            // Each declarator is in scope for the ones after it, as in the interpreter.
+            Statement::Let(declarators) => {
+                let mut code = Vec::new();
+                for (id, t, expr) in declarators {
+                    code.push(match expr {
+                        Some(expr) => format!("{} = {}", id, self.expr(expr)),
+                        None => id.clone(),
+                    });
+                    let t = match t {
+                        Some(t) => t.parse().ok(),
+                        None => expr.as_deref().and_then(|expr| self.int_type(expr)),
+                    };
+                    self.declare(id, t);
+                }
+                format!("let {};", code.join(", "))
+            }
+            Statement::Const(id, t, expr) => {
+                let line = format!("const {} = {};", id, self.expr(expr));
+                self.declare(id, t.parse().ok());
+                line
+            }
+            Statement::Destructure(ids, expr) => {
+                let line = format!("let [{}] = {};", ids.join(", "), self.expr(expr));
+                let elements: &[Box<Expr>] = match &**expr {
+                    Expr::Tuple(elements) => elements,
+                    _ => &[],
+                };
+                for (i, id) in ids.iter().enumerate() {
+                    let t = elements.get(i).and_then(|element| self.int_type(element));
+                    self.declare(id, t);
+                }
+                line
+            }
+            Statement::Print(args) => format!("console.log({});", self.args(args)),
+            Statement::Block(_) | Statement::For(_, _, _, _) | Statement::DoWhile(_, _) => {
+                unreachable!("only single-line statements can be for loop initializers")
+            }
+        }
+    }
+
+    // The integer type an expression has, or None for floats, literals and everything else.
+    fn int_type(&self, expr: &Expr) -> Option<NumType> {
+        match expr {
+            Expr::Id(id)
+            | Expr::PreIncrement(id)
+            | Expr::PreDecrement(id)
+            | Expr::PostIncrement(id)
+            | Expr::PostDecrement(id)
+            | Expr::Eq(id, _) => self.lookup(id),
+            Expr::Parenthesis(inner)
+            | Expr::Neg(inner)
+            | Expr::BitNot(inner)
+            | Expr::Shl(inner, _)
+            | Expr::Shr(inner, _) => self.int_type(inner),
+            Expr::Cast(_, t) | Expr::Saturate(_, t) => Some(*t).filter(|t| t.is_integer()),
+            Expr::Exponent(l, r)
+            | Expr::Multiply(l, r)
+            | Expr::Divide(l, r)
+            | Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::BitAnd(l, r)
+            | Expr::BitXor(l, r)
+            | Expr::BitOr(l, r)
+            | Expr::Ternary(_, l, r) => self.int_type(l).or_else(|| self.int_type(r)),
+            Expr::Call(name, args)
+                if ["abs", "floor", "ceil", "round", "min", "max", "clamp"]
+                    .contains(&name.as_str()) =>
+            {
+                args.iter().find_map(|arg| self.int_type(arg))
+            }
+            _ => None,
+        }
+    }
+
+    // Whether a cast operand converts as an integer: a typed one, or a whole literal with any signs
+    // and `~` in front, which the interpreter also converts exactly rather than as an f64.
+    fn integral(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Number(Literal::Int(_)) => true,
+            Expr::Neg(inner) | Expr::BitNot(inner) | Expr::Parenthesis(inner) => {
+                self.integral(inner)
+            }
+            _ => self.int_type(expr).is_some(),
+        }
+    }
+
+    // Integer results are brought back into range with a bitwise operator, which JS applies to
+    // 32-bit integers, so wrapping works as in the interpreter's wrapping mode. Wider types are
+    // left alone: doubles can't hold them exactly anyway. Returns the operator and its
+    // precedence, as Expr::precedence numbers them.
+    fn wrapping(t: NumType) -> Option<(&'static str, u8)> {
+        match t {
+            NumType::U8 => Some(("& 255", 10)),
+            NumType::U16 => Some(("& 65535", 10)),
+            NumType::I8 => Some(("<< 24 >> 24", 7)),
+            NumType::I16 => Some(("<< 16 >> 16", 7)),
+            NumType::U32 => Some((">>> 0", 7)),
+            NumType::I32 => Some(("| 0", 12)),
+            _ => None,
+        }
+    }
+
+    // The type whose wraparound `expr` has to spell out, if any. In-range operands keep
+    // JS's bitwise results in range, except that they are signed 32-bit, which u32 isn't.
+    fn wrapped(&self, expr: &Expr) -> Option<NumType> {
+        let t = match expr {
+            Expr::Exponent(_, _)
+            | Expr::Multiply(_, _)
+            | Expr::Divide(_, _)
+            | Expr::Add(_, _)
+            | Expr::Sub(_, _)
+            | Expr::Neg(_)
+            | Expr::BitNot(_)
+            | Expr::Shl(_, _)
+            | Expr::PostIncrement(_)
+            | Expr::PostDecrement(_) => self.int_type(expr),
+            Expr::Cast(inner, t) if self.integral(inner) => Some(*t),
+            Expr::BitAnd(_, _) | Expr::BitXor(_, _) | Expr::BitOr(_, _) => {
+                self.int_type(expr).filter(|t| *t == NumType::U32)
+            }
+            _ => None,
+        };
+        t.filter(|t| Self::wrapping(*t).is_some())
+    }
+
+    // How tightly the code for `expr` binds before any wraparound is applied. Casts to floats
+    // vanish, and integer casts and divisions become Math.trunc calls, so none of them keep the
+    // precedence of their operator.
+    fn raw_precedence(&self, expr: &Expr) -> u8 {
+        match expr {
+            Expr::Cast(inner, t) if t.is_float() || self.integral(inner) => self.precedence(inner),
+            Expr::Cast(_, _) => 0,
+            Expr::Saturate(inner, t) if t.is_float() => self.precedence(inner),
+            Expr::Divide(_, _) if self.int_type(expr).is_some() => 0,
+            // Written as an assignment, see `expr`.
+            Expr::PreIncrement(id) | Expr::PreDecrement(id)
+                if self.lookup(id).and_then(Self::wrapping).is_some() =>
+            {
+                16
+            }
+            Expr::PostIncrement(id) | Expr::PostDecrement(id)
+                if self.lookup(id).and_then(Self::wrapping).is_some() =>
+            {
+                6
+            }
+            _ => expr.precedence(),
+        }
+    }
+
+    fn precedence(&self, expr: &Expr) -> u8 {
+        match self.wrapped(expr).and_then(Self::wrapping) {
+            Some((_, precedence)) => precedence,
+            None => self.raw_precedence(expr),
+        }
+    }
+
+    fn wrap(&self, expr: &Expr, code: String) -> String {
+        let Some((op, precedence)) = self.wrapped(expr).and_then(Self::wrapping) else {
+            return code;
+        };
+        if self.raw_precedence(expr) > precedence {
+            format!("({}) {}", code, op)
+        } else {
+            format!("{} {}", code, op)
+        }
+    }
+
+    // A statement or for loop step drops the value, so `x++` can be written like `++x`.
+    fn discarded(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::PostIncrement(id) => self.expr(&Expr::PreIncrement(id.clone())),
+            Expr::PostDecrement(id) => self.expr(&Expr::PreDecrement(id.clone())),
+            _ => self.expr(expr),
+        }
+    }
+
+    fn operand(&self, parent: &Expr, operand: &Expr, tighter: bool) -> String {
+        let code = self.expr(operand);
+        let (parent, operand) = (self.raw_precedence(parent), self.precedence(operand));
+        if operand > parent || (tighter && operand == parent && operand > 0) {
+            format!("({})", code)
+        } else {
+            code
+        }
+    }
+
+    fn binary(&self, parent: &Expr, l: &Expr, op: &str, r: &Expr, right_assoc: bool) -> String {
+        format!(
+            "{} {} {}",
+            self.operand(parent, l, right_assoc),
+            op,
+            self.operand(parent, r, !right_assoc)
+        )
+    }
+
+    fn args(&self, args: &[Box<Expr>]) -> String {
+        args.iter()
+            .map(|arg| self.expr(arg))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn expr(&self, expr: &Expr) -> String {
+        self.wrap(expr, self.raw(expr))
+    }
+
+    fn raw(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::PreIncrement(id)
+            | Expr::PreDecrement(id)
+            | Expr::PostIncrement(id)
+            | Expr::PostDecrement(id)
+                if let Some((op, _)) = self.lookup(id).and_then(Self::wrapping) =>
+            {
+                let sign = match expr {
+                    Expr::PreIncrement(_) | Expr::PostIncrement(_) => "+",
+                    _ => "-",
+                };
+                let assign = format!("{} = {} {} 1 {}", id, id, sign, op);
+                match expr {
+                    Expr::PreIncrement(_) | Expr::PreDecrement(_) => assign,
+                    // The stored value, stepped back and wrapped again by `wrap`.
+                    _ => format!("({}) {} 1", assign, if sign == "+" { "-" } else { "+" }),
+                }
+            }
+            Expr::Number(_)
+            | Expr::Str(_)
+            | Expr::Bool(_)
+            | Expr::Id(_)
+            | Expr::PreIncrement(_)
+            | Expr::PreDecrement(_)
+            | Expr::PostIncrement(_)
+            | Expr::PostDecrement(_) => expr.to_string(),
+            Expr::PI => "Math.PI".to_owned(),
+            Expr::E => "Math.E".to_owned(),
+            // JS has no Math.TAU, so it is written out.
+            Expr::Constant(Constant::Tau) => format!("{:?}", std::f64::consts::TAU),
+            Expr::Constant(Constant::Sqrt2) => "Math.SQRT2".to_owned(),
+            Expr::Constant(Constant::Ln2) => "Math.LN2".to_owned(),
+            Expr::Constant(Constant::Ln10) => "Math.LN10".to_owned(),
+            Expr::Constant(Constant::Infinity) => "Infinity".to_owned(),
+            Expr::Constant(Constant::NaN) => "NaN".to_owned(),
+            Expr::Call(name, args) => self.call(name, args),
+            // JS has no tuples; an array destructures the same way.
+            Expr::Array(elements) | Expr::Tuple(elements) => format!("[{}]", self.args(elements)),
+            Expr::Index(array, index) => {
+                format!("{}[{}]", self.operand(expr, array, false), self.expr(index))
+            }
+            Expr::Length(array) => format!("{}.length", self.operand(expr, array, false)),
+            Expr::Template(parts) => {
+                let mut code = String::from("`");
+                for part in parts {
+                    match part {
+                        TemplatePart::Str(s) => code.push_str(&escape_template(s)),
+                        TemplatePart::Expr(inner) => {
+                            code.push_str(&format!("${{{}}}", self.expr(inner)))
+                        }
+                    }
+                }
+                code.push('`');
+                code
+            }
+            // The string methods exist in JS under the same names.
+            Expr::Method(receiver, name, args) => format!(
+                "{}.{}({})",
+                self.operand(expr, receiver, false),
+                name,
+                self.args(args)
+            ),
+            Expr::Parenthesis(inner) => format!("({})", self.expr(inner)),
+            // JS rejects a negative literal directly on the left of `**`.
+            Expr::Exponent(l, r) => match **l {
+                Expr::Number(Literal::Float(n)) if n.is_sign_negative() => {
+                    format!("({}) ** {}", n, self.operand(expr, r, false))
+                }
+                _ => self.binary(expr, l, "**", r, true),
+            },
+            Expr::Multiply(l, r) => self.binary(expr, l, "*", r, false),
+            // Integer division truncates toward zero, as in Rust.
+            Expr::Divide(l, r) if self.int_type(expr).is_some() => {
+                format!("Math.trunc({})", self.binary(expr, l, "/", r, false))
+            }
+            Expr::Divide(l, r) => self.binary(expr, l, "/", r, false),
+            Expr::Add(l, r) => self.binary(expr, l, "+", r, false),
+            Expr::Sub(l, r) => self.binary(expr, l, "-", r, false),
+            // Only the truncation of an integer cast survives; JS has a single number type.
+            Expr::Cast(inner, t) if t.is_float() => self.expr(inner),
+            // Between integer types only the wraparound is left, which `wrap` adds.
+            Expr::Cast(inner, _) if self.integral(inner) => self.expr(inner),
+            Expr::Cast(inner, _) => format!("Math.trunc({})", self.expr(inner)),
+            Expr::Saturate(inner, t) => match t.int_range() {
+                Some((min, max)) => format!(
+                    "Math.min(Math.max(Math.trunc({}), {}), {})",
+                    self.expr(inner),
+                    min,
+                    max
+                ),
+                None => self.expr(inner),
+            },
+            // JS's typeof names its own types ("number", "boolean", "object"), not ours.
+            Expr::Not(inner) | Expr::Neg(inner) | Expr::TypeOf(inner) | Expr::BitNot(inner) => {
+                let op = match expr {
+                    Expr::Not(_) => "!",
+                    Expr::Neg(_) => "-",
+                    Expr::TypeOf(_) => "typeof ",
+                    _ => "~",
+                };
+                // Nor may a unary operator apply to an unparenthesized `**`.
+                let code = match **inner {
+                    Expr::Exponent(_, _) => format!("({})", self.expr(inner)),
+                    _ => self.operand(expr, inner, false),
+                };
+                let space = if op == "-" && code.starts_with('-') {
+                    " "
+                } else {
+                    ""
+                };
+                format!("{}{}{}", op, space, code)
+            }
+            Expr::Shl(l, r) => self.binary(expr, l, "<<", r, false),
+            // JS's `>>` works on signed 32-bit integers, so u32 needs the unsigned shift.
+            Expr::Shr(l, r) if self.int_type(expr) == Some(NumType::U32) => {
+                self.binary(expr, l, ">>>", r, false)
+            }
+            Expr::Shr(l, r) => self.binary(expr, l, ">>", r, false),
+            Expr::Less(l, r) => self.binary(expr, l, "<", r, false),
+            Expr::LessEq(l, r) => self.binary(expr, l, "<=", r, false),
+            Expr::Greater(l, r) => self.binary(expr, l, ">", r, false),
+            Expr::GreaterEq(l, r) => self.binary(expr, l, ">=", r, false),
+            // The interpreter never coerces between types, which is what the strict forms do.
+            Expr::Equal(l, r) => self.binary(expr, l, "===", r, false),
+            Expr::NotEqual(l, r) => self.binary(expr, l, "!==", r, false),
+            Expr::BitAnd(l, r) => self.binary(expr, l, "&", r, false),
+            Expr::BitXor(l, r) => self.binary(expr, l, "^", r, false),
+            Expr::BitOr(l, r) => self.binary(expr, l, "|", r, false),
+            Expr::And(l, r) => self.binary(expr, l, "&&", r, false),
+            Expr::Or(l, r) => self.binary(expr, l, "||", r, false),
+            Expr::Ternary(c, a, b) => format!(
+                "{} ? {} : {}",
+                self.operand(expr, c, true),
+                self.operand(expr, a, true),
+                self.operand(expr, b, false)
+            ),
+            Expr::Eq(id, value) => format!("{} = {}", id, self.expr(value)),
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Box<Expr>]) -> String {
+        match name {
+            "sqrt" | "abs" | "floor" | "ceil" | "round" | "min" | "max" => {
+                format!("Math.{}({})", name, self.args(args))
+            }
+            "clamp" if args.len() == 3 => format!(
+                "Math.min(Math.max({}, {}), {})",
+                self.expr(&args[0]),
+                self.expr(&args[1]),
+                self.expr(&args[2])
+            ),
+            "includes" if args.len() == 2 => {
+                let array = self.expr(&args[0]);
+                let array = if self.precedence(&args[0]) > 1 {
+                    format!("({})", array)
+                } else {
+                    array
+                };
+                format!("{}.includes({})", array, self.expr(&args[1]))
+            }
+            // console.assert only logs a failure rather than stopping the program.
+            "assert" => format!("console.assert({})", self.args(args)),
+            // Math.*, parseInt, parseFloat, isNaN and isFinite already exist under those names.
+            _ => format!("{}({})", name, self.args(args)),
+        }
+    }
+}
+
+// Emits the program as plain JavaScript, with every numeric type collapsed to `number`. Integer
+// division still truncates, and integers of up to 32 bits wrap as in the wrapping overflow mode.
+pub fn to_js(stmts: &[Box<Spanned<Statement>>]) -> String {
+    let mut writer = JsWriter {
+        out: String::new(),
+        indent: 0,
+        scopes: vec![HashMap::new()],
+    };
+    for statement in stmts {
+        writer.statement(statement);
+    }
+    writer.out
+}
@@ -0,0 +1,86 @@
+use crate::{
+    ast::{Declarator, Expr, Spanned, Statement},
+    visit::{AstVisitor, walk_statement},
+};
+use std::fmt::Write;
+
+struct DotWriter {
+    out: String,
+    next_id: usize,
+}
+impl DotWriter {
+    fn node(&mut self, label: &str, children: Vec<usize>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(self.out, "    n{} [label=\"{}\"];", id, label).unwrap();
+        for child in children {
+            writeln!(self.out, "    n{} -> n{};", id, child).unwrap();
+        }
+        id
+    }
+
+    fn declarator(&mut self, (name, t, expr): &Declarator) -> usize {
+        let children = expr.iter().map(|expr| self.visit_expr(expr)).collect();
+        match t {
+            Some(t) => self.node(&format!("Let {}:{}", name, t), children),
+            None => self.node(&format!("Let {}", name), children),
+        }
+    }
+}
+// Children are emitted before their parents, so every edge points at a node already declared.
+impl AstVisitor for DotWriter {
+    type Output = usize;
+
+    // A lone declarator keeps the single-node form; several hang off one Let node.
+    fn visit_statement(&mut self, statement: &Statement) -> usize {
+        let Statement::Let(declarators) = statement else {
+            return walk_statement(self, statement);
+        };
+        if let [declarator] = &declarators[..] {
+            return self.declarator(declarator);
+        }
+        let children = declarators.iter().map(|d| self.declarator(d)).collect();
+        self.node("Let", children)
+    }
+
+    fn statement(&mut self, statement: &Statement, children: Vec<usize>) -> usize {
+        match statement {
+            Statement::Const(name, t, _) => self.node(&format!("Const {}:{}", name, t), children),
+            Statement::Destructure(names, _) => {
+                self.node(&format!("Let [{}]", names.join(", ")), children)
+            }
+            _ => self.node(statement.name(), children),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, children: Vec<usize>) -> usize {
+        let label = match expr {
+            Expr::Number(n) => format!("Number({})", n),
+            Expr::Str(s) => format!("Str({:?})", s),
+            Expr::Bool(b) => format!("Bool({})", b),
+            Expr::Id(name)
+            | Expr::PreIncrement(name)
+            | Expr::PreDecrement(name)
+            | Expr::PostIncrement(name)
+            | Expr::PostDecrement(name) => format!("{}({:?})", expr.name(), name),
+            Expr::Call(name, _) | Expr::Method(_, name, _) => format!("{} {}", expr.name(), name),
+            Expr::Cast(_, t) | Expr::Saturate(_, t) => format!("{} {}", expr.name(), t),
+            Expr::Eq(name, _) => format!("Eq {}", name),
+            _ => expr.name().to_owned(),
+        };
+        self.node(&label, children)
+    }
+}
+
+pub fn to_dot(stmts: &[Box<Spanned<Statement>>]) -> String {
+    let mut writer = DotWriter {
+        out: String::from("digraph AST {\n"),
+        next_id: 0,
+    };
+    for statement in stmts {
+        writer.visit_statement(statement);
+    }
+    writer.out.push_str("}\n");
+    writer.out
+}
@@ -0,0 +1,244 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    IntLiteral,
+    FloatLiteral,
+    StrLiteral,
+    Keyword,
+    Operator,
+    Punct,
+    /// `// ...` to the end of the line, or `/* ... */`.
+    Comment,
+    /// Anything the grammar has no token for, including an unterminated string.
+    Error,
+}
+
+// Keep these in sync with the literal tokens in ts2g.lalrpop. Type names and the built-in
+// constants count as keywords, since an editor would highlight them the same way.
+const KEYWORDS: &[&str] = &[
+    "let", "const", "for", "do", "while", "print", "true", "false", "typeof", "as", "saturate",
+    "Math", "pi", "PI", "e", "TAU", "SQRT_2", "LN_2", "LN_10", "INFINITY", "NAN", "u8", "i8",
+    "u16", "i16", "u32", "i32", "u64", "i64", "usize", "isize", "u128", "i128", "f32", "f64",
+    "string", "bool",
+];
+// Longest first, so "**" wins over "*".
+const OPERATORS: &[&str] = &[
+    "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "<", ">",
+    "=", "!", "~", "&", "^", "|", "?", ":",
+];
+const PUNCTS: &[char] = &['(', ')', '{', '}', '[', ']', ';', ',', '.'];
+
+// Splits source into tokens with their byte ranges, for editor tooling. Unlike the parser this
+// never fails: text it can't make sense of becomes Error tokens and lexing carries on.
+pub fn tokenize(source: &str) -> Vec<(TokenKind, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = source[pos..].chars().next() {
+        let rest = &source[pos..];
+        let (kind, len) = if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        } else if c.is_ascii_alphabetic() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            // Like the generated lexer, take the longest match: "u8" is a type even though
+            // identifiers stop at the digit.
+            match KEYWORDS
+                .iter()
+                .filter(|keyword| rest.starts_with(*keyword) && keyword.len() >= len)
+                .map(|keyword| keyword.len())
+                .max()
+            {
+                Some(len) => (TokenKind::Keyword, len),
+                None => (TokenKind::Ident, len),
+            }
+        } else if c.is_ascii_digit() {
+            number(rest)
+        } else if c == '"' || c == '`' {
+            string(rest)
+        } else if rest.starts_with("//") || rest.starts_with("/*") {
+            comment(rest)
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            (TokenKind::Operator, op.len())
+        } else if PUNCTS.contains(&c) {
+            (TokenKind::Punct, 1)
+        } else {
+            (TokenKind::Error, c.len_utf8())
+        };
+        tokens.push((kind, pos..pos + len));
+        pos += len;
+    }
+    tokens
+}
+
+// Keywords that need more after them, so a statement can't end on one.
+const OPENING_KEYWORDS: &[&str] = &[
+    "let", "const", "for", "do", "while", "print", "typeof", "as", "saturate", "Math",
+];
+
+// Basic automatic semicolon insertion, as a pass over the tokens rather than in the grammar,
+// where an optional `;` would conflict with every operator that can also start an expression.
+// A `;` goes at a line break outside parentheses and brackets when the line ends in something
+// that can end a statement and the next line starts with something that can't continue it. As in
+// JS, `a` then `-b` on the next line stays one expression, while `a` then `++b` doesn't. Inserted
+// semicolons sit right after the last token, so line numbers and the columns of later lines are
+// unchanged. Also returns where each inserted `;` ended up in the new text, so byte offsets into
+// it can be mapped back onto the source.
+pub fn insert_semicolons(source: &str) -> (String, Vec<usize>) {
+    let tokens: Vec<_> = tokenize(source)
+        .into_iter()
+        .filter(|(kind, _)| *kind != TokenKind::Comment)
+        .collect();
+    let mut out = String::with_capacity(source.len());
+    let mut inserted = Vec::new();
+    let mut copied = 0;
+    let mut depth = 0usize;
+    for (i, (kind, range)) in tokens.iter().enumerate() {
+        let text = &source[range.clone()];
+        match text {
+            "(" | "[" => depth += 1,
+            ")" | "]" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        let ends = match kind {
+            TokenKind::Ident
+            | TokenKind::IntLiteral
+            | TokenKind::FloatLiteral
+            | TokenKind::StrLiteral => true,
+            TokenKind::Keyword => !OPENING_KEYWORDS.contains(&text),
+            TokenKind::Operator => text == "++" || text == "--",
+            TokenKind::Punct => text == ")" || text == "]",
+            TokenKind::Comment | TokenKind::Error => false,
+        };
+        let breaks = match tokens.get(i + 1) {
+            None => true,
+            Some((next_kind, next)) => {
+                let next_text = &source[next.clone()];
+                source[range.end..next.start].contains('\n')
+                    && match next_kind {
+                        TokenKind::Keyword => next_text != "as",
+                        TokenKind::Operator => ["++", "--", "!", "~"].contains(&next_text),
+                        TokenKind::Punct => next_text == "}",
+                        _ => true,
+                    }
+            }
+        };
+        if ends && breaks && depth == 0 {
+            out.push_str(&source[copied..range.end]);
+            inserted.push(out.len());
+            out.push(';');
+            copied = range.end;
+        }
+    }
+    out.push_str(&source[copied..]);
+    (out, inserted)
+}
+
+// `[0-9]+(\.[0-9]+)?`, `0x[0-9a-fA-F]+` or `0b[01]+`; a dot without digits after it is left for
+// the next token.
+fn number(rest: &str) -> (TokenKind, usize) {
+    for (prefix, radix) in [("0x", 16), ("0b", 2)] {
+        if let Some(after) = rest.strip_prefix(prefix) {
+            let len = after
+                .find(|c: char| !c.is_digit(radix))
+                .unwrap_or(after.len());
+            if len > 0 {
+                return (TokenKind::IntLiteral, 2 + len);
+            }
+        }
+    }
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let int = digits(rest);
+    match rest[int..].strip_prefix('.') {
+        Some(fraction) if digits(fraction) > 0 => {
+            (TokenKind::FloatLiteral, int + 1 + digits(fraction))
+        }
+        _ => (TokenKind::IntLiteral, int),
+    }
+}
+
+// An unterminated block comment runs to the end of the input as a single Error token.
+fn comment(rest: &str) -> (TokenKind, usize) {
+    if rest.starts_with("//") {
+        return (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()));
+    }
+    match rest[2..].find("*/") {
+        Some(end) => (TokenKind::Comment, end + 4),
+        None => (TokenKind::Error, rest.len()),
+    }
+}
+
+// Double-quoted strings and whole template literals, interpolations included. A template runs
+// past the backticks of any template nested in its `${...}`. An unterminated string runs to the
+// end of the input as a single Error token.
+fn string(rest: &str) -> (TokenKind, usize) {
+    match string_len(rest) {
+        Some(len) => (TokenKind::StrLiteral, len),
+        None => (TokenKind::Error, rest.len()),
+    }
+}
+
+fn string_len(rest: &str) -> Option<usize> {
+    let quote = rest.chars().next()?;
+    let mut i = quote.len_utf8();
+    while let Some(c) = rest[i..].chars().next() {
+        match c {
+            c if c == quote => return Some(i + 1),
+            '\\' => i += rest[i + 1..].chars().next().map_or(0, char::len_utf8),
+            '$' if quote == '`' && rest[i..].starts_with("${") => {
+                i += 2 + interpolation_len(&rest[i + 2..])?;
+                continue;
+            }
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    None
+}
+
+// The length of an interpolation's source up to and including its closing `}`. Braces nest, and
+// braces inside strings don't count.
+fn interpolation_len(rest: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = 0;
+    while let Some(c) = rest[i..].chars().next() {
+        match c {
+            '"' | '`' => {
+                i += string_len(&rest[i..])?;
+                continue;
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i + 1),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    None
+}
+
+// Where the first template nested inside another one starts. The parser's template token ends at
+// the first unescaped backtick, so it can't take these; finding them here gives a clearer error
+// than whatever the parser would make of the rest of the outer template.
+pub fn nested_template(source: &str) -> Option<usize> {
+    tokenize(source).into_iter().find_map(|(kind, range)| {
+        let text = &source[range.clone()];
+        if kind != TokenKind::StrLiteral || !text.starts_with('`') {
+            return None;
+        }
+        let mut chars = text.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '`' => return (i + 1 < text.len()).then_some(range.start + i),
+                '\\' => {
+                    chars.next();
+                }
+                _ => {}
+            }
+        }
+        None
+    })
+}
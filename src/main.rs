@@ -1,20 +1,37 @@
 pub mod ast;
+mod builtins;
+pub mod codegen;
+pub mod dot;
+pub mod lexer;
+pub mod stats;
+#[cfg(test)]
+mod tests;
+pub mod visit;
+pub mod warnings;
 
 use crate::{
-    ast::{Expr, Statement},
+    ast::{Expr, Literal, Node, NumType, Span, Spanned, Statement, TemplatePart},
+    builtins::Rng,
     ts2g::SParser,
+    warnings::Warning,
 };
 use core::f64;
-use lalrpop_util::lalrpop_mod;
-use stopwatch::Stopwatch;
+use lalrpop_util::{ParseError, lalrpop_mod};
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
     error::Error,
     fmt::Display,
+    fs,
+    io::{self, Read},
     ops::{Add, Div, Mul, Sub},
+    process,
+    time::{Duration, Instant},
 };
 
-lalrpop_mod!(ts2g);
+// The generated parser isn't held to the crate's lints.
+lalrpop_mod!(#[allow(clippy::all)] ts2g);
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -27,45 +44,22 @@ union Number {
     i32: i32,
     u64: u64,
     i64: i64,
+    usize: usize,
+    isize: isize,
+    u128: u128,
+    i128: i128,
     f32: f32,
     f64: f64,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum NumType {
-    U8,
-    I8,
-    U16,
-    I16,
-    U32,
-    I32,
-    U64,
-    I64,
-    F32,
-    F64,
-}
-impl Display for NumType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            NumType::U8 => write!(f, "u8"),
-            NumType::I8 => write!(f, "i8"),
-            NumType::U16 => write!(f, "u16"),
-            NumType::I16 => write!(f, "i16"),
-            NumType::U32 => write!(f, "u32"),
-            NumType::I32 => write!(f, "i32"),
-            NumType::U64 => write!(f, "u64"),
-            NumType::I64 => write!(f, "i64"),
-            NumType::F32 => write!(f, "f32"),
-            NumType::F64 => write!(f, "f64"),
-        }
-    }
-}
-
 #[derive(Clone, Copy)]
-struct Value {
+pub struct Value {
     v: Number,
     t: NumType,
 }
+// Floats print in their shortest round-tripping form, so whole values drop the fraction (2.0
+// prints as 2, like JS) and 1.0/3.0 prints all 16 digits. A formatter precision (`{:.3}`) fixes
+// the number of decimals instead; integers ignore it.
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.t {
@@ -77,510 +71,2446 @@ impl Display for Value {
             NumType::I32 => write!(f, "{}", unsafe { self.v.i32 }),
             NumType::U64 => write!(f, "{}", unsafe { self.v.u64 }),
             NumType::I64 => write!(f, "{}", unsafe { self.v.i64 }),
-            NumType::F32 => write!(f, "{}", unsafe { self.v.f32 }),
-            NumType::F64 => write!(f, "{}", unsafe { self.v.f64 }),
+            NumType::Usize => write!(f, "{}", unsafe { self.v.usize }),
+            NumType::Isize => write!(f, "{}", unsafe { self.v.isize }),
+            NumType::U128 => write!(f, "{}", unsafe { self.v.u128 }),
+            NumType::I128 => write!(f, "{}", unsafe { self.v.i128 }),
+            NumType::F32 => fmt_float(unsafe { self.v.f32 }, f),
+            NumType::F64 => fmt_float(unsafe { self.v.f64 }, f),
+        }
+    }
+}
+
+// The alternate flag (`{:#}`) switches floats outside JS's plain-decimal range, [1e-6, 1e21), to
+// JS-style scientific notation such as `1e+21` or `1.5e-7`. Precision applies to the mantissa.
+// Infinities are spelled as in JS, `Infinity` and `-Infinity`, rather than Rust's `inf`.
+fn fmt_float<T>(x: T, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+where
+    T: Copy + Display + std::fmt::LowerExp + Into<f64>,
+{
+    let x64: f64 = x.into();
+    if x64.is_infinite() {
+        // pad_integral rather than pad, which would cut the word to the precision.
+        return f.pad_integral(x64 > 0.0, "", "Infinity");
+    }
+    let magnitude = x64.abs();
+    if !f.alternate()
+        || magnitude == 0.0
+        || !magnitude.is_finite()
+        || (1e-6..1e21).contains(&magnitude)
+    {
+        return Display::fmt(&x, f);
+    }
+    let s = match f.precision() {
+        Some(digits) => format!("{:.*e}", digits, x),
+        None => format!("{:e}", x),
+    };
+    match s.split_once('e') {
+        Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+            write!(f, "{}e+{}", mantissa, exponent)
         }
+        _ => f.write_str(&s),
     }
 }
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let v: &dyn std::fmt::Debug = match self.t {
+            NumType::U8 => unsafe { &self.v.u8 },
+            NumType::I8 => unsafe { &self.v.i8 },
+            NumType::U16 => unsafe { &self.v.u16 },
+            NumType::I16 => unsafe { &self.v.i16 },
+            NumType::U32 => unsafe { &self.v.u32 },
+            NumType::I32 => unsafe { &self.v.i32 },
+            NumType::U64 => unsafe { &self.v.u64 },
+            NumType::I64 => unsafe { &self.v.i64 },
+            NumType::Usize => unsafe { &self.v.usize },
+            NumType::Isize => unsafe { &self.v.isize },
+            NumType::U128 => unsafe { &self.v.u128 },
+            NumType::I128 => unsafe { &self.v.i128 },
+            NumType::F32 => unsafe { &self.v.f32 },
+            NumType::F64 => unsafe { &self.v.f64 },
+        };
+        f.debug_struct("Value")
+            .field("t", &self.t)
+            .field("v", v)
+            .finish()
+    }
+}
+// The operators are the checked forms, so a mismatched pair, an overflow or a division by zero
+// is an error rather than a panic.
 impl Add for Value {
-    type Output = Self;
+    type Output = Result<Self, InterpError>;
 
     fn add(self, rhs: Self) -> Self::Output {
-        if self.t != rhs.t {
-            panic!("Types {} and {} are not the same.", self.t, rhs.t)
-        }
+        self.checked_add(rhs)
+    }
+}
+impl Sub for Value {
+    type Output = Result<Self, InterpError>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.checked_sub(rhs)
+    }
+}
+impl Mul for Value {
+    type Output = Result<Self, InterpError>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.checked_mul(rhs)
+    }
+}
+impl Div for Value {
+    type Output = Result<Self, InterpError>;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.checked_div(rhs)
+    }
+}
+impl Value {
+    pub fn is_integer(self) -> bool {
+        self.t.is_integer()
+    }
+    pub fn is_float(self) -> bool {
+        self.t.is_float()
+    }
+
+    // Typed constants for accumulators and `x + 1`-style rewrites.
+    pub fn zero(t: NumType) -> Self {
+        Self::from_i128(0, t)
+    }
+    pub fn one(t: NumType) -> Self {
+        Self::from_i128(1, t)
+    }
 
+    // Integer powers are computed exactly rather than through f64, wrapping like the other
+    // arithmetic operators. Operands must already share a type.
+    pub fn pow(self, rhs: Self, policy: OverflowPolicy) -> Result<Self, InterpError> {
         match self.t {
-            NumType::U8 => Self {
-                t: self.t,
-                v: Number {
-                    u8: unsafe { self.v.u8 } + unsafe { rhs.v.u8 },
-                },
-            },
-            NumType::I8 => Self {
-                t: self.t,
-                v: Number {
-                    i8: unsafe { self.v.i8 } + unsafe { rhs.v.i8 },
-                },
-            },
-            NumType::U16 => Self {
-                t: self.t,
-                v: Number {
-                    u16: unsafe { self.v.u16 } + unsafe { rhs.v.u16 },
-                },
-            },
-            NumType::I16 => Self {
+            NumType::F32 => Ok(Self {
                 t: self.t,
                 v: Number {
-                    i16: unsafe { self.v.i16 } + unsafe { rhs.v.i16 },
+                    f32: unsafe { self.v.f32 }.powf(unsafe { rhs.v.f32 }),
                 },
-            },
-            NumType::U32 => Self {
-                t: self.t,
-                v: Number {
-                    u32: unsafe { self.v.u32 } + unsafe { rhs.v.u32 },
-                },
-            },
-            NumType::I32 => Self {
-                t: self.t,
-                v: Number {
-                    i32: unsafe { self.v.i32 } + unsafe { rhs.v.i32 },
-                },
-            },
-            NumType::U64 => Self {
-                t: self.t,
-                v: Number {
-                    u64: unsafe { self.v.u64 } + unsafe { rhs.v.u64 },
-                },
-            },
-            NumType::I64 => Self {
+            }),
+            NumType::F64 => Ok(Self {
                 t: self.t,
                 v: Number {
-                    i64: unsafe { self.v.i64 } + unsafe { rhs.v.i64 },
-                },
-            },
-            NumType::F32 => Self {
-                t: self.t,
-                v: Number {
-                    f32: unsafe { self.v.f32 } + unsafe { rhs.v.f32 },
-                },
-            },
-            NumType::F64 => Self {
-                t: self.t,
-                v: Number {
-                    f64: unsafe { self.v.f64 } + unsafe { rhs.v.f64 },
+                    f64: unsafe { self.v.f64 }.powf(unsafe { rhs.v.f64 }),
                 },
-            },
+            }),
+            _ => {
+                let exponent = rhs.integer()?;
+                let exponent = u32::try_from(exponent).map_err(|_| {
+                    InterpError::InvalidArgument(format!(
+                        "Integer exponent {} is negative or too large.",
+                        exponent
+                    ))
+                })?;
+                let overflow = || InterpError::Overflow("**", self.t);
+                if self.t == NumType::U128 {
+                    let base = unsafe { self.v.u128 };
+                    let n = match policy {
+                        OverflowPolicy::Checked => base.checked_pow(exponent),
+                        OverflowPolicy::Wrapping => Some(base.wrapping_pow(exponent)),
+                        OverflowPolicy::Saturating => Some(base.saturating_pow(exponent)),
+                    };
+                    return Ok(Self::from(n.ok_or_else(overflow)?));
+                }
+                let base = self.integer()?;
+                let n = match policy {
+                    OverflowPolicy::Checked => base.checked_pow(exponent),
+                    OverflowPolicy::Wrapping => Some(base.wrapping_pow(exponent)),
+                    OverflowPolicy::Saturating => Some(base.saturating_pow(exponent)),
+                };
+                Self::fit(n.ok_or_else(overflow)?, self.t, policy).ok_or_else(overflow)
+            }
         }
     }
 }
-impl Sub for Value {
-    type Output = Self;
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        if self.t != rhs.t {
-            panic!("Types {} and {} are not the same.", self.t, rhs.t)
+// A literal operand with the minus signs written in front of it. The sign is kept apart because
+// a whole literal's u128 can't always be negated in place.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SignedLiteral {
+    negative: bool,
+    literal: Literal,
+}
+impl SignedLiteral {
+    fn as_f64(self) -> f64 {
+        let n = self.literal.as_f64();
+        if self.negative { -n } else { n }
+    }
+}
+impl Display for SignedLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", if self.negative { "-" } else { "" }, self.literal)
+    }
+}
+
+// What integer arithmetic does when the result doesn't fit its type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // Stop with InterpError::Overflow.
+    #[default]
+    Checked,
+    // Keep the low bits, as the hardware would.
+    Wrapping,
+    // Clamp to the type's minimum or maximum.
+    Saturating,
+}
+
+// Whether operators accept numbers of different types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoercionMode {
+    // Both operands must already have the same type; mixing them is a TypeMismatch.
+    #[default]
+    Strict,
+    // Mixed operands are widened by Value::coerce_pair's promotion rules where that is lossless.
+    Loose,
+}
+
+// The binary operators the overflow policy applies to, besides `**` with its u32 exponent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Arith {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+impl Arith {
+    fn symbol(self) -> &'static str {
+        match self {
+            Arith::Add => "+",
+            Arith::Sub => "-",
+            Arith::Mul => "*",
+            Arith::Div => "/",
         }
+    }
 
-        match self.t {
-            NumType::U8 => Self {
-                t: self.t,
-                v: Number {
-                    u8: unsafe { self.v.u8 } - unsafe { rhs.v.u8 },
-                },
-            },
-            NumType::I8 => Self {
-                t: self.t,
-                v: Number {
-                    i8: unsafe { self.v.i8 } - unsafe { rhs.v.i8 },
-                },
-            },
-            NumType::U16 => Self {
-                t: self.t,
-                v: Number {
-                    u16: unsafe { self.v.u16 } - unsafe { rhs.v.u16 },
-                },
-            },
-            NumType::I16 => Self {
-                t: self.t,
-                v: Number {
-                    i16: unsafe { self.v.i16 } - unsafe { rhs.v.i16 },
-                },
-            },
-            NumType::U32 => Self {
-                t: self.t,
-                v: Number {
-                    u32: unsafe { self.v.u32 } - unsafe { rhs.v.u32 },
-                },
-            },
-            NumType::I32 => Self {
-                t: self.t,
-                v: Number {
-                    i32: unsafe { self.v.i32 } - unsafe { rhs.v.i32 },
-                },
-            },
-            NumType::U64 => Self {
-                t: self.t,
-                v: Number {
-                    u64: unsafe { self.v.u64 } - unsafe { rhs.v.u64 },
-                },
-            },
-            NumType::I64 => Self {
-                t: self.t,
-                v: Number {
-                    i64: unsafe { self.v.i64 } - unsafe { rhs.v.i64 },
-                },
-            },
-            NumType::F32 => Self {
-                t: self.t,
-                v: Number {
-                    f32: unsafe { self.v.f32 } - unsafe { rhs.v.f32 },
+    // None only when a checked operation overflows. A wrapped or saturated result may still be
+    // out of range for a narrower operand type, which `fit` then handles the same way.
+    fn int(self, a: i128, b: i128, policy: OverflowPolicy) -> Option<i128> {
+        match (self, policy) {
+            (Arith::Add, OverflowPolicy::Checked) => a.checked_add(b),
+            (Arith::Add, OverflowPolicy::Wrapping) => Some(a.wrapping_add(b)),
+            (Arith::Add, OverflowPolicy::Saturating) => Some(a.saturating_add(b)),
+            (Arith::Sub, OverflowPolicy::Checked) => a.checked_sub(b),
+            (Arith::Sub, OverflowPolicy::Wrapping) => Some(a.wrapping_sub(b)),
+            (Arith::Sub, OverflowPolicy::Saturating) => Some(a.saturating_sub(b)),
+            (Arith::Mul, OverflowPolicy::Checked) => a.checked_mul(b),
+            (Arith::Mul, OverflowPolicy::Wrapping) => Some(a.wrapping_mul(b)),
+            (Arith::Mul, OverflowPolicy::Saturating) => Some(a.saturating_mul(b)),
+            (Arith::Div, OverflowPolicy::Checked) => a.checked_div(b),
+            (Arith::Div, OverflowPolicy::Wrapping) => Some(a.wrapping_div(b)),
+            (Arith::Div, OverflowPolicy::Saturating) => Some(a.saturating_div(b)),
+        }
+    }
+
+    fn uint(self, a: u128, b: u128, policy: OverflowPolicy) -> Option<u128> {
+        match (self, policy) {
+            (Arith::Add, OverflowPolicy::Checked) => a.checked_add(b),
+            (Arith::Add, OverflowPolicy::Wrapping) => Some(a.wrapping_add(b)),
+            (Arith::Add, OverflowPolicy::Saturating) => Some(a.saturating_add(b)),
+            (Arith::Sub, OverflowPolicy::Checked) => a.checked_sub(b),
+            (Arith::Sub, OverflowPolicy::Wrapping) => Some(a.wrapping_sub(b)),
+            (Arith::Sub, OverflowPolicy::Saturating) => Some(a.saturating_sub(b)),
+            (Arith::Mul, OverflowPolicy::Checked) => a.checked_mul(b),
+            (Arith::Mul, OverflowPolicy::Wrapping) => Some(a.wrapping_mul(b)),
+            (Arith::Mul, OverflowPolicy::Saturating) => Some(a.saturating_mul(b)),
+            (Arith::Div, _) => Some(a / b),
+        }
+    }
+
+    // Operands must already share a float type.
+    fn float(self, a: Value, b: Value) -> Value {
+        let v = if a.t == NumType::F32 {
+            let (x, y) = unsafe { (a.v.f32, b.v.f32) };
+            Number {
+                f32: match self {
+                    Arith::Add => x + y,
+                    Arith::Sub => x - y,
+                    Arith::Mul => x * y,
+                    Arith::Div => x / y,
                 },
-            },
-            NumType::F64 => Self {
-                t: self.t,
-                v: Number {
-                    f64: unsafe { self.v.f64 } - unsafe { rhs.v.f64 },
+            }
+        } else {
+            let (x, y) = unsafe { (a.v.f64, b.v.f64) };
+            Number {
+                f64: match self {
+                    Arith::Add => x + y,
+                    Arith::Sub => x - y,
+                    Arith::Mul => x * y,
+                    Arith::Div => x / y,
                 },
-            },
-        }
+            }
+        };
+        Value { t: a.t, v }
     }
 }
-impl Mul for Value {
-    type Output = Self;
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        if self.t != rhs.t {
-            panic!("Types {} and {} are not the same.", self.t, rhs.t)
+impl Value {
+    // Promotion rules for mixed-type operands. Only lossless widening is done implicitly:
+    // - integer with float: the float type, widened to f64 for integers over 16 bits
+    // - f32 with f64: f64
+    // - integers of the same signedness: the wider type
+    // - unsigned with signed: the signed type, only if it is strictly wider
+    // Anything else (e.g. u32 with i32) is a type mismatch.
+    pub fn coerce_pair(a: Self, b: Self) -> Result<(Self, Self), InterpError> {
+        if a.t == b.t {
+            return Ok((a, b));
         }
 
+        let t = match (a.t.is_float(), b.t.is_float()) {
+            (true, true) => NumType::F64,
+            (true, false) | (false, true) => {
+                let (float, int) = if a.t.is_float() {
+                    (a.t, b.t)
+                } else {
+                    (b.t, a.t)
+                };
+                if float == NumType::F32 && int.bit_width() <= 16 {
+                    NumType::F32
+                } else {
+                    NumType::F64
+                }
+            }
+            (false, false) => {
+                let (wide, narrow) = if a.t.bit_width() >= b.t.bit_width() {
+                    (a.t, b.t)
+                } else {
+                    (b.t, a.t)
+                };
+                if wide.is_signed() == narrow.is_signed()
+                    || (wide.is_signed() && wide.bit_width() > narrow.bit_width())
+                {
+                    wide
+                } else {
+                    return Err(InterpError::TypeMismatch(a.t.to_string(), b.t.to_string()));
+                }
+            }
+        };
+        Ok((a.cast(t), b.cast(t)))
+    }
+
+    pub fn cast(self, target: NumType) -> Self {
+        // Every integer type's bits fit in an i128 and every float fits in an f64, so going
+        // through them gives the same result as a direct Rust `as` conversion. Only u128 to
+        // float needs the unsigned value rather than its bits.
         match self.t {
-            NumType::U8 => Self {
-                t: self.t,
-                v: Number {
-                    u8: unsafe { self.v.u8 } * unsafe { rhs.v.u8 },
-                },
-            },
-            NumType::I8 => Self {
-                t: self.t,
-                v: Number {
-                    i8: unsafe { self.v.i8 } * unsafe { rhs.v.i8 },
-                },
-            },
-            NumType::U16 => Self {
-                t: self.t,
-                v: Number {
-                    u16: unsafe { self.v.u16 } * unsafe { rhs.v.u16 },
-                },
-            },
-            NumType::I16 => Self {
-                t: self.t,
-                v: Number {
-                    i16: unsafe { self.v.i16 } * unsafe { rhs.v.i16 },
-                },
-            },
-            NumType::U32 => Self {
-                t: self.t,
-                v: Number {
-                    u32: unsafe { self.v.u32 } * unsafe { rhs.v.u32 },
-                },
-            },
-            NumType::I32 => Self {
-                t: self.t,
-                v: Number {
-                    i32: unsafe { self.v.i32 } * unsafe { rhs.v.i32 },
-                },
-            },
-            NumType::U64 => Self {
-                t: self.t,
-                v: Number {
-                    u64: unsafe { self.v.u64 } * unsafe { rhs.v.u64 },
-                },
-            },
-            NumType::I64 => Self {
-                t: self.t,
-                v: Number {
-                    i64: unsafe { self.v.i64 } * unsafe { rhs.v.i64 },
-                },
-            },
-            NumType::F32 => Self {
-                t: self.t,
-                v: Number {
-                    f32: unsafe { self.v.f32 } * unsafe { rhs.v.f32 },
-                },
-            },
-            NumType::F64 => Self {
-                t: self.t,
-                v: Number {
-                    f64: unsafe { self.v.f64 } * unsafe { rhs.v.f64 },
-                },
-            },
+            NumType::F32 => Self::from_f64(unsafe { self.v.f32 as f64 }, target),
+            NumType::F64 => Self::from_f64(unsafe { self.v.f64 }, target),
+            NumType::U128 if target.is_float() => {
+                Self::from_f64(unsafe { self.v.u128 as f64 }, target)
+            }
+            _ => Self::from_i128(self.as_i128().unwrap(), target),
         }
     }
-}
-impl Div for Value {
-    type Output = Self;
 
-    fn div(self, rhs: Self) -> Self::Output {
-        if self.t != rhs.t {
-            panic!("Types {} and {} are not the same.", self.t, rhs.t)
+    // Float to integer `as` already saturates (NaN becomes 0), so only integer sources need
+    // clamping. Floats only overflow into infinity, which is left alone.
+    pub fn saturate(self, target: NumType) -> Self {
+        let Some((min, max)) = target.int_range() else {
+            return self.cast(target);
+        };
+        match self.t {
+            NumType::F32 | NumType::F64 => self.cast(target),
+            NumType::U128 => {
+                let n = unsafe { self.v.u128 };
+                Self::from_i128(n.min(max) as i128, target)
+            }
+            _ => {
+                let n = self.as_i128().unwrap();
+                if n < min {
+                    Self::from_i128(min, target)
+                } else if n >= 0 && n as u128 > max {
+                    Self::from_i128(max as i128, target)
+                } else {
+                    Self::from_i128(n, target)
+                }
+            }
         }
+    }
+
+    // Integers are computed in i128 (u128 in its own type) and fitted back into the operand type
+    // as the policy says; floats go through the plain operators, since they overflow to infinity
+    // instead.
+    fn arith(self, rhs: Self, op: Arith, policy: OverflowPolicy) -> Result<Self, InterpError> {
+        let (lhs, rhs) = Self::coerce_pair(self, rhs)?;
+        let overflow = || InterpError::Overflow(op.symbol(), lhs.t);
+        match lhs.t {
+            NumType::F32 | NumType::F64 => Ok(op.float(lhs, rhs)),
+            NumType::U128 => {
+                let (a, b) = unsafe { (lhs.v.u128, rhs.v.u128) };
+                if op == Arith::Div && b == 0 {
+                    return Err(InterpError::DivideByZero);
+                }
+                Ok(Self::from(op.uint(a, b, policy).ok_or_else(overflow)?))
+            }
+            t => {
+                let (a, b) = (lhs.as_i128().unwrap(), rhs.as_i128().unwrap());
+                if op == Arith::Div && b == 0 {
+                    return Err(InterpError::DivideByZero);
+                }
+                let n = op.int(a, b, policy).ok_or_else(overflow)?;
+                Self::fit(n, t, policy).ok_or_else(overflow)
+            }
+        }
+    }
+
+    // Puts a widened result back into an integer type: wrapping truncates, saturating clamps
+    // and checked refuses anything out of range.
+    fn fit(n: i128, t: NumType, policy: OverflowPolicy) -> Option<Self> {
+        let (min, max) = t.int_range()?;
+        if n >= min && (n < 0 || n as u128 <= max) {
+            return Some(Self::from_i128(n, t));
+        }
+        match policy {
+            OverflowPolicy::Checked => None,
+            OverflowPolicy::Wrapping => Some(Self::from_i128(n, t)),
+            OverflowPolicy::Saturating => {
+                Some(Self::from_i128(if n < min { min } else { max as i128 }, t))
+            }
+        }
+    }
+
+    pub fn checked_neg(self) -> Result<Self, InterpError> {
+        self.neg_with_policy(OverflowPolicy::Checked)
+    }
 
+    // The same arithmetic the interpreter does, with the overflow behaviour fixed by the name.
+    // Mixed operands are widened with coerce_pair first, so even the wrapping and saturating forms
+    // fail on types it can't reconcile. Floats ignore the policy and overflow to infinity.
+    pub fn checked_add(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Add, OverflowPolicy::Checked)
+    }
+    pub fn wrapping_add(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Add, OverflowPolicy::Wrapping)
+    }
+    pub fn saturating_add(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Add, OverflowPolicy::Saturating)
+    }
+    pub fn checked_sub(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Sub, OverflowPolicy::Checked)
+    }
+    pub fn wrapping_sub(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Sub, OverflowPolicy::Wrapping)
+    }
+    pub fn saturating_sub(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Sub, OverflowPolicy::Saturating)
+    }
+    pub fn checked_mul(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Mul, OverflowPolicy::Checked)
+    }
+    pub fn wrapping_mul(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Mul, OverflowPolicy::Wrapping)
+    }
+    pub fn saturating_mul(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Mul, OverflowPolicy::Saturating)
+    }
+    pub fn checked_div(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Div, OverflowPolicy::Checked)
+    }
+    pub fn wrapping_div(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Div, OverflowPolicy::Wrapping)
+    }
+    pub fn saturating_div(self, rhs: Self) -> Result<Self, InterpError> {
+        self.arith(rhs, Arith::Div, OverflowPolicy::Saturating)
+    }
+
+    // Integers are negated as 0 - x, so negating i8::MIN or a non-zero unsigned value overflows
+    // like any subtraction would. Floats just flip their sign, which keeps -0.0 distinct from 0.0.
+    pub fn neg_with_policy(self, policy: OverflowPolicy) -> Result<Self, InterpError> {
         match self.t {
-            NumType::U8 => Self {
-                t: self.t,
-                v: Number {
-                    u8: unsafe { self.v.u8 } / unsafe { rhs.v.u8 },
-                },
-            },
-            NumType::I8 => Self {
-                t: self.t,
-                v: Number {
-                    i8: unsafe { self.v.i8 } / unsafe { rhs.v.i8 },
-                },
-            },
-            NumType::U16 => Self {
-                t: self.t,
-                v: Number {
-                    u16: unsafe { self.v.u16 } / unsafe { rhs.v.u16 },
-                },
-            },
-            NumType::I16 => Self {
-                t: self.t,
-                v: Number {
-                    i16: unsafe { self.v.i16 } / unsafe { rhs.v.i16 },
-                },
-            },
-            NumType::U32 => Self {
-                t: self.t,
-                v: Number {
-                    u32: unsafe { self.v.u32 } / unsafe { rhs.v.u32 },
-                },
-            },
-            NumType::I32 => Self {
-                t: self.t,
-                v: Number {
-                    i32: unsafe { self.v.i32 } / unsafe { rhs.v.i32 },
-                },
-            },
-            NumType::U64 => Self {
-                t: self.t,
-                v: Number {
-                    u64: unsafe { self.v.u64 } / unsafe { rhs.v.u64 },
-                },
-            },
-            NumType::I64 => Self {
+            NumType::F32 => Ok(Self {
                 t: self.t,
                 v: Number {
-                    i64: unsafe { self.v.i64 } / unsafe { rhs.v.i64 },
+                    f32: -unsafe { self.v.f32 },
                 },
-            },
-            NumType::F32 => Self {
-                t: self.t,
-                v: Number {
-                    f32: unsafe { self.v.f32 } / unsafe { rhs.v.f32 },
-                },
-            },
-            NumType::F64 => Self {
+            }),
+            NumType::F64 => Ok(Self {
                 t: self.t,
                 v: Number {
-                    f64: unsafe { self.v.f64 } / unsafe { rhs.v.f64 },
+                    f64: -unsafe { self.v.f64 },
                 },
+            }),
+            t => Self::zero(t).arith(self, Arith::Sub, policy),
+        }
+    }
+
+    // Integers widened to i128 (sign- or zero-extended, u128 reinterpreted); None for floats.
+    fn as_i128(self) -> Option<i128> {
+        match self.t {
+            NumType::U8 => Some(unsafe { self.v.u8 as i128 }),
+            NumType::I8 => Some(unsafe { self.v.i8 as i128 }),
+            NumType::U16 => Some(unsafe { self.v.u16 as i128 }),
+            NumType::I16 => Some(unsafe { self.v.i16 as i128 }),
+            NumType::U32 => Some(unsafe { self.v.u32 as i128 }),
+            NumType::I32 => Some(unsafe { self.v.i32 as i128 }),
+            NumType::U64 => Some(unsafe { self.v.u64 as i128 }),
+            NumType::I64 => Some(unsafe { self.v.i64 as i128 }),
+            NumType::Usize => Some(unsafe { self.v.usize as i128 }),
+            NumType::Isize => Some(unsafe { self.v.isize as i128 }),
+            NumType::U128 => Some(unsafe { self.v.u128 as i128 }),
+            NumType::I128 => Some(unsafe { self.v.i128 }),
+            NumType::F32 | NumType::F64 => None,
+        }
+    }
+    fn integer(self) -> Result<i128, InterpError> {
+        self.as_i128()
+            .ok_or_else(|| InterpError::ExpectedType("an integer".to_owned(), self.t.to_string()))
+    }
+
+    // Bitwise operators act on the widened value; truncating back to the operand type keeps
+    // exactly the bits the native operator would produce. Operands must already share a type.
+    pub fn bitwise(self, rhs: Self, op: fn(i128, i128) -> i128) -> Result<Self, InterpError> {
+        Ok(Self::from_i128(op(self.integer()?, rhs.integer()?), self.t))
+    }
+    pub fn bitnot(self) -> Result<Self, InterpError> {
+        Ok(Self::from_i128(!self.integer()?, self.t))
+    }
+    // Shifts keep the left operand's type. `>>` is arithmetic for signed types and logical for
+    // unsigned ones, since the widened value is sign- or zero-extended accordingly; u128 has
+    // no room to be zero-extended and is shifted natively. An amount of the bit width or more
+    // is an error when checked, is taken modulo the width when wrapping, and shifts every bit
+    // out when saturating. Negative amounts are always an error.
+    pub fn shift(
+        self,
+        amount: Self,
+        left: bool,
+        policy: OverflowPolicy,
+    ) -> Result<Self, InterpError> {
+        let n = self.integer()?;
+        let amount = amount.integer()?;
+        let width = self.t.bit_width() as i128;
+        let out_of_range = || {
+            InterpError::InvalidArgument(format!(
+                "Shift amount {} is out of range for {}.",
+                amount, self.t
+            ))
+        };
+        if amount < 0 {
+            return Err(out_of_range());
+        }
+        let amount = match policy {
+            _ if amount < width => amount,
+            OverflowPolicy::Checked => return Err(out_of_range()),
+            OverflowPolicy::Wrapping => amount % width,
+            // Everything is shifted out: only zeros, or copies of the sign bit, are left.
+            OverflowPolicy::Saturating => {
+                let fill = if !left && self.t.is_signed() && n < 0 { -1 } else { 0 };
+                return Ok(Self::from_i128(fill, self.t));
+            }
+        };
+        Ok(Self::from_i128(
+            match (left, self.t) {
+                (true, _) => n << amount,
+                (false, NumType::U128) => ((n as u128) >> amount) as i128,
+                (false, _) => n >> amount,
             },
+            self.t,
+        ))
+    }
+
+    fn from_i128(n: i128, t: NumType) -> Self {
+        let v = match t {
+            NumType::U8 => Number { u8: n as u8 },
+            NumType::I8 => Number { i8: n as i8 },
+            NumType::U16 => Number { u16: n as u16 },
+            NumType::I16 => Number { i16: n as i16 },
+            NumType::U32 => Number { u32: n as u32 },
+            NumType::I32 => Number { i32: n as i32 },
+            NumType::U64 => Number { u64: n as u64 },
+            NumType::I64 => Number { i64: n as i64 },
+            NumType::Usize => Number { usize: n as usize },
+            NumType::Isize => Number { isize: n as isize },
+            NumType::U128 => Number { u128: n as u128 },
+            NumType::I128 => Number { i128: n },
+            NumType::F32 => Number { f32: n as f32 },
+            NumType::F64 => Number { f64: n as f64 },
+        };
+        Self { v, t }
+    }
+
+    // Every numeric literal form goes through here, so they share one range check: integer
+    // types only accept whole literals within their bounds. Whole literals are checked exactly.
+    fn from_literal(lit: SignedLiteral, t: NumType) -> Result<Self, InterpError> {
+        let out_of_range = || InterpError::LiteralOutOfRange(lit.to_string(), t);
+        let n = match lit.literal {
+            Literal::Int(magnitude) if t.is_integer() => {
+                let (min, max) = t.int_range().unwrap();
+                return if !lit.negative && magnitude <= max {
+                    Ok(match t {
+                        NumType::U128 => Self::from(magnitude),
+                        _ => Self::from_i128(magnitude as i128, t),
+                    })
+                } else if lit.negative && magnitude <= min.unsigned_abs() {
+                    Ok(Self::from_i128((magnitude as i128).wrapping_neg(), t))
+                } else {
+                    Err(out_of_range())
+                };
+            }
+            _ => lit.as_f64(),
+        };
+        if t.is_float() {
+            return Ok(Self::from_f64(n, t));
+        }
+        // Powers of two are exact in f64, unlike e.g. u64::MAX.
+        let (min, end) = if t.is_signed() {
+            let half = 2f64.powi(t.bit_width() as i32 - 1);
+            (-half, half)
+        } else {
+            (0.0, 2f64.powi(t.bit_width() as i32))
+        };
+        if n.fract() == 0.0 && n >= min && n < end {
+            Ok(Self::from_f64(n, t))
+        } else {
+            Err(out_of_range())
+        }
+    }
+
+    fn from_f64(n: f64, t: NumType) -> Self {
+        let v = match t {
+            NumType::U8 => Number { u8: n as u8 },
+            NumType::I8 => Number { i8: n as i8 },
+            NumType::U16 => Number { u16: n as u16 },
+            NumType::I16 => Number { i16: n as i16 },
+            NumType::U32 => Number { u32: n as u32 },
+            NumType::I32 => Number { i32: n as i32 },
+            NumType::U64 => Number { u64: n as u64 },
+            NumType::I64 => Number { i64: n as i64 },
+            NumType::Usize => Number { usize: n as usize },
+            NumType::Isize => Number { isize: n as isize },
+            NumType::U128 => Number { u128: n as u128 },
+            NumType::I128 => Number { i128: n as i128 },
+            NumType::F32 => Number { f32: n as f32 },
+            NumType::F64 => Number { f64: n },
+        };
+        Self { v, t }
+    }
+}
+
+impl From<u8> for Value {
+    fn from(n: u8) -> Self {
+        Self {
+            t: NumType::U8,
+            v: Number { u8: n },
         }
     }
 }
-impl Value {
-    pub fn powf(self, rhs: Self) -> Self {
-        if self.t != rhs.t {
-            panic!("Types {} and {} are not the same.", self.t, rhs.t)
+impl From<i8> for Value {
+    fn from(n: i8) -> Self {
+        Self {
+            t: NumType::I8,
+            v: Number { i8: n },
+        }
+    }
+}
+impl From<u16> for Value {
+    fn from(n: u16) -> Self {
+        Self {
+            t: NumType::U16,
+            v: Number { u16: n },
+        }
+    }
+}
+impl From<i16> for Value {
+    fn from(n: i16) -> Self {
+        Self {
+            t: NumType::I16,
+            v: Number { i16: n },
+        }
+    }
+}
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Self {
+            t: NumType::U32,
+            v: Number { u32: n },
+        }
+    }
+}
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Self {
+            t: NumType::I32,
+            v: Number { i32: n },
+        }
+    }
+}
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Self {
+            t: NumType::U64,
+            v: Number { u64: n },
+        }
+    }
+}
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Self {
+            t: NumType::I64,
+            v: Number { i64: n },
+        }
+    }
+}
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Self {
+            t: NumType::Usize,
+            v: Number { usize: n },
+        }
+    }
+}
+impl From<isize> for Value {
+    fn from(n: isize) -> Self {
+        Self {
+            t: NumType::Isize,
+            v: Number { isize: n },
+        }
+    }
+}
+impl From<u128> for Value {
+    fn from(n: u128) -> Self {
+        Self {
+            t: NumType::U128,
+            v: Number { u128: n },
+        }
+    }
+}
+impl From<i128> for Value {
+    fn from(n: i128) -> Self {
+        Self {
+            t: NumType::I128,
+            v: Number { i128: n },
+        }
+    }
+}
+impl From<f32> for Value {
+    fn from(n: f32) -> Self {
+        Self {
+            t: NumType::F32,
+            v: Number { f32: n },
+        }
+    }
+}
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Self {
+            t: NumType::F64,
+            v: Number { f64: n },
+        }
+    }
+}
+// Values of different NumTypes are unordered and unequal; callers coerce first when they want
+// mixed-type comparisons. Floats follow IEEE 754, so NaN != NaN.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.t != other.t {
+            return None;
         }
-
         match self.t {
-            NumType::U8 => Self {
-                t: self.t,
-                v: Number {
-                    u8: unsafe { self.v.u8 as f64 }.powf(unsafe { rhs.v.u8 } as f64) as u8,
-                },
-            },
-            NumType::I8 => Self {
-                t: self.t,
-                v: Number {
-                    i8: unsafe { self.v.i8 as f64 }.powf(unsafe { rhs.v.i8 } as f64) as i8,
-                },
-            },
-            NumType::U16 => Self {
-                t: self.t,
-                v: Number {
-                    u16: unsafe { self.v.u16 as f64 }.powf(unsafe { rhs.v.u16 } as f64) as u16,
-                },
-            },
-            NumType::I16 => Self {
-                t: self.t,
-                v: Number {
-                    i16: unsafe { self.v.i16 as f64 }.powf(unsafe { rhs.v.i16 } as f64) as i16,
-                },
-            },
-            NumType::U32 => Self {
-                t: self.t,
-                v: Number {
-                    u32: unsafe { self.v.u32 as f64 }.powf(unsafe { rhs.v.u32 } as f64) as u32,
-                },
-            },
-            NumType::I32 => Self {
-                t: self.t,
-                v: Number {
-                    i32: unsafe { self.v.i32 as f64 }.powf(unsafe { rhs.v.i32 } as f64) as i32,
-                },
-            },
-            NumType::U64 => Self {
-                t: self.t,
-                v: Number {
-                    u64: unsafe { self.v.u64 as f64 }.powf(unsafe { rhs.v.u64 } as f64) as u64,
-                },
-            },
-            NumType::I64 => Self {
-                t: self.t,
-                v: Number {
-                    i64: unsafe { self.v.i64 as f64 }.powf(unsafe { rhs.v.i64 } as f64) as i64,
-                },
-            },
-            NumType::F32 => Self {
-                t: self.t,
-                v: Number {
-                    f32: unsafe { self.v.f32 as f64 }.powf(unsafe { rhs.v.f32 } as f64) as f32,
-                },
-            },
-            NumType::F64 => Self {
-                t: self.t,
-                v: Number {
-                    f64: unsafe { self.v.f64 }.powf(unsafe { rhs.v.f64 }),
-                },
-            },
+            NumType::U8 => unsafe { self.v.u8.partial_cmp(&other.v.u8) },
+            NumType::I8 => unsafe { self.v.i8.partial_cmp(&other.v.i8) },
+            NumType::U16 => unsafe { self.v.u16.partial_cmp(&other.v.u16) },
+            NumType::I16 => unsafe { self.v.i16.partial_cmp(&other.v.i16) },
+            NumType::U32 => unsafe { self.v.u32.partial_cmp(&other.v.u32) },
+            NumType::I32 => unsafe { self.v.i32.partial_cmp(&other.v.i32) },
+            NumType::U64 => unsafe { self.v.u64.partial_cmp(&other.v.u64) },
+            NumType::I64 => unsafe { self.v.i64.partial_cmp(&other.v.i64) },
+            NumType::Usize => unsafe { self.v.usize.partial_cmp(&other.v.usize) },
+            NumType::Isize => unsafe { self.v.isize.partial_cmp(&other.v.isize) },
+            NumType::U128 => unsafe { self.v.u128.partial_cmp(&other.v.u128) },
+            NumType::I128 => unsafe { self.v.i128.partial_cmp(&other.v.i128) },
+            NumType::F32 => unsafe { self.v.f32.partial_cmp(&other.v.f32) },
+            NumType::F64 => unsafe { self.v.f64.partial_cmp(&other.v.f64) },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InterpError {
+    TypeMismatch(String, String),
+    ExpectedType(String, String),
+    Undefined(String),
+    UsedBeforeAssignment(String),
+    AssignToConst(String),
+    ConstRedeclared(String),
+    DivideByZero,
+    // The operator and the type its result didn't fit.
+    Overflow(&'static str, NumType),
+    StackUnderflow,
+    StepLimitExceeded(u64),
+    TimeLimitExceeded(Duration),
+    RecursionLimitExceeded(usize),
+    IndexOutOfBounds(i128, usize),
+    // Names in a destructuring `let`, then elements in the array it was given.
+    DestructureMismatch(usize, usize),
+    LiteralOutOfRange(String, NumType),
+    UnknownFunction(String),
+    // A function that returns nothing was used where a value is needed.
+    VoidValue(String),
+    // A tuple was used anywhere but as the value of a destructuring `let`.
+    MisplacedTuple,
+    InvalidArgument(String),
+    AssertionFailed(Option<String>),
+}
+impl Display for InterpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpError::TypeMismatch(a, b) => write!(f, "Types {} and {} are not the same.", a, b),
+            InterpError::ExpectedType(expected, found) => {
+                write!(f, "Expected {} but found {}.", expected, found)
+            }
+            InterpError::Undefined(id) => write!(f, "Variable {} is not declared.", id),
+            InterpError::UsedBeforeAssignment(id) => {
+                write!(f, "Variable {} is used before being assigned.", id)
+            }
+            InterpError::AssignToConst(id) => write!(f, "cannot assign to constant {}", id),
+            InterpError::ConstRedeclared(id) => write!(f, "Constant {} is already declared.", id),
+            InterpError::DivideByZero => write!(f, "Division by zero."),
+            InterpError::Overflow(op, t) => write!(f, "Overflow in {} for type {}.", op, t),
+            InterpError::StackUnderflow => write!(f, "stack underflow"),
+            InterpError::TimeLimitExceeded(budget) => {
+                write!(f, "Execution exceeded the time limit of {:?}.", budget)
+            }
+            InterpError::StepLimitExceeded(max) => {
+                write!(f, "Execution exceeded the limit of {} steps.", max)
+            }
+            InterpError::RecursionLimitExceeded(max) => {
+                write!(f, "Nesting exceeded the limit of {} levels.", max)
+            }
+            InterpError::IndexOutOfBounds(index, len) => {
+                write!(f, "Index {} is out of bounds for length {}.", index, len)
+            }
+            InterpError::DestructureMismatch(names, len) => {
+                write!(
+                    f,
+                    "Cannot destructure an array of length {} into {} names.",
+                    len, names
+                )
+            }
+            InterpError::LiteralOutOfRange(n, t) => {
+                write!(f, "literal {} out of range for {}", n, t)
+            }
+            InterpError::UnknownFunction(name) => write!(f, "Unknown function {}.", name),
+            InterpError::VoidValue(name) => write!(f, "Function {} does not return a value.", name),
+            InterpError::MisplacedTuple => {
+                write!(
+                    f,
+                    "Tuples can only be destructured, as in let [a, b] = (1, 2);"
+                )
+            }
+            InterpError::InvalidArgument(msg) => write!(f, "{}", msg),
+            InterpError::AssertionFailed(Some(msg)) => write!(f, "Assertion failed: {}", msg),
+            InterpError::AssertionFailed(None) => write!(f, "Assertion failed."),
+        }
+    }
+}
+impl Error for InterpError {}
+
+// Objects are values, not references: assigning an array to another variable copies it, so
+// later changes through one name are never visible through the other.
+#[derive(Clone, Debug)]
+pub enum Object {
+    Number(Value),
+    Bool(bool),
+    Str(String),
+    // Elements all share one NumType.
+    Array(Vec<Value>),
+}
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::Number(v) => Display::fmt(v, f),
+            Object::Bool(b) => write!(f, "{}", b),
+            Object::Str(s) => write!(f, "{}", s),
+            Object::Array(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    Display::fmt(v, f)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+impl Object {
+    fn type_name(&self) -> String {
+        match self {
+            Object::Number(v) => v.t.to_string(),
+            Object::Bool(_) => "bool".to_owned(),
+            Object::Str(_) => "string".to_owned(),
+            Object::Array(_) => "array".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Scope {
+    vars: HashMap<String, Object>,
+    // Names declared without a value, until the first assignment.
+    unassigned: HashSet<String>,
+    // The annotated type of each binding that has one, which every assignment is checked against.
+    types: HashMap<String, String>,
+    consts: HashSet<String>,
+}
+
+impl Scope {
+    fn set_type(&mut self, id: &str, t: Option<String>) {
+        match t {
+            Some(t) => self.types.insert(id.to_owned(), t),
+            None => self.types.remove(id),
+        };
+    }
+}
+
+// Every variable and const binding at one point in time, for TS2G::restore.
+#[derive(Clone)]
+pub struct Snapshot {
+    scopes: Vec<Scope>,
+}
+
+// What this build of the language supports, for tools that generate source for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub loops: bool,
+    /// User-defined functions; only the built-ins can be called so far.
+    pub functions: bool,
+    pub strings: bool,
+    pub templates: bool,
+    pub arrays: bool,
+    pub num_types: Vec<NumType>,
+}
+
+// Everything a call to TS2G::evaluate produced.
+#[derive(Debug)]
+pub struct EvalResult {
+    /// Each line `print` wrote, in order.
+    pub printed: Vec<String>,
+    /// The global variables once the program finished, sorted by name so output is stable.
+    pub vars: BTreeMap<String, Object>,
+    pub parse_duration: Duration,
+    pub eval_duration: Duration,
+    pub warnings: Vec<Warning>,
+}
+
+pub struct TS2G {
+    // What the static checks found in the program last passed to `run`.
+    warnings: Vec<Warning>,
+    // Where the last run failed, if it did.
+    error_span: Option<Span>,
+    // Innermost scope last; there is always at least the global scope.
+    scopes: Vec<Scope>,
+    stack: Vec<Object>,
+    // Statements and expressions visited so far in the current run, checked against max_steps.
+    steps: u64,
+    max_steps: Option<u64>,
+    time_budget: Option<Duration>,
+    // When the current run's time budget runs out.
+    deadline: Option<Instant>,
+    // Statements and expressions currently being visited, nested inside each other.
+    depth: usize,
+    max_depth: usize,
+    overflow: OverflowPolicy,
+    coercion: CoercionMode,
+    // Decimals `print` shows for floats; None keeps the default Display form.
+    float_precision: Option<usize>,
+    // Whether `print` formats floats with `{:#}`, switching extreme magnitudes to e-notation.
+    scientific: bool,
+    rng: Rng,
+    // Collects `print` output while `evaluate` runs, so it can be returned as well as shown.
+    printed: Option<Vec<String>>,
+}
+
+// An unoptimised build spends about 10KB of native stack per level, so this stays well inside
+// the main thread's 8MB while still being deeper than hand-written programs nest.
+const DEFAULT_MAX_DEPTH: usize = 256;
+
+// Reading the clock costs far more than a step, so the time budget is only checked this often.
+const STEPS_PER_CLOCK_CHECK: u64 = 1024;
+
+// Every interpreter option lives here; `TS2G::init()` is the all-defaults shortcut.
+#[derive(Clone, Debug)]
+pub struct Ts2gBuilder {
+    overflow: OverflowPolicy,
+    coercion: CoercionMode,
+    max_steps: Option<u64>,
+    time_budget: Option<Duration>,
+    max_depth: usize,
+    float_precision: Option<usize>,
+    scientific: bool,
+    seed: Option<u64>,
+}
+impl Default for Ts2gBuilder {
+    fn default() -> Self {
+        Self {
+            overflow: OverflowPolicy::default(),
+            coercion: CoercionMode::default(),
+            max_steps: None,
+            time_budget: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            float_precision: None,
+            scientific: false,
+            seed: None,
         }
     }
 }
+impl Ts2gBuilder {
+    // How integer arithmetic and negation treat results that don't fit; Checked by default.
+    pub fn overflow(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
 
-pub struct TS2G {
-    unit: (),
-    _errors: (),
-    vars: HashMap<String, Value>,
-    stack: Vec<Value>,
+    // Whether mixed numeric types are widened or rejected; Strict by default.
+    pub fn coercion(mut self, mode: CoercionMode) -> Self {
+        self.coercion = mode;
+        self
+    }
+
+    // Aborts runaway programs after `max` visited statements and expressions.
+    pub fn max_steps(mut self, max: u64) -> Self {
+        self.max_steps = Some(max);
+        self
+    }
+
+    // Aborts a run once it has taken longer than `budget` of wall-clock time. The clock is only
+    // read every STEPS_PER_CLOCK_CHECK steps, so a run can overshoot by however long those take.
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    // How deeply statements and expressions may nest before RecursionLimitExceeded.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    pub fn float_precision(mut self, digits: usize) -> Self {
+        self.float_precision = Some(digits);
+        self
+    }
+
+    // Has `print` show very large and very small floats in scientific notation, like JS does.
+    pub fn scientific(mut self, on: bool) -> Self {
+        self.scientific = on;
+        self
+    }
+
+    // Makes Math.random reproducible; without it the generator is seeded from the clock.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> TS2G {
+        TS2G {
+            warnings: Vec::new(),
+            error_span: None,
+            scopes: vec![Scope::default()],
+            stack: Vec::new(),
+            steps: 0,
+            max_steps: self.max_steps,
+            time_budget: self.time_budget,
+            deadline: None,
+            depth: 0,
+            max_depth: self.max_depth,
+            overflow: self.overflow,
+            coercion: self.coercion,
+            float_precision: self.float_precision,
+            scientific: self.scientific,
+            rng: self.seed.map_or_else(Rng::from_time, Rng::new),
+            printed: None,
+        }
+    }
 }
+
 impl TS2G {
     pub fn init() -> Self {
-        Self {
-            unit: (),
-            _errors: (),
-            vars: HashMap::new(),
-            stack: Vec::new(),
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Ts2gBuilder {
+        Ts2gBuilder::default()
+    }
+
+    pub fn capabilities() -> Capabilities {
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION"),
+            loops: true,
+            functions: false,
+            strings: true,
+            templates: true,
+            arrays: true,
+            num_types: NumType::ALL.to_vec(),
+        }
+    }
+
+    // Forgets everything a previous program left behind while keeping the configuration and the
+    // allocated capacity.
+    pub fn reset(&mut self) {
+        self.scopes.truncate(1);
+        let global = &mut self.scopes[0];
+        global.vars.clear();
+        global.unassigned.clear();
+        global.types.clear();
+        global.consts.clear();
+        self.stack.clear();
+        self.steps = 0;
+        self.deadline = None;
+        self.depth = 0;
+        self.warnings.clear();
+        self.error_span = None;
+        self.printed = None;
+    }
+
+    // Defines (or overwrites) a global before running a program that refers to it.
+    pub fn set_var(&mut self, name: &str, value: Value) {
+        self.scopes[0]
+            .vars
+            .insert(name.to_owned(), Object::Number(value));
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            scopes: self.scopes.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.scopes = snapshot.scopes;
+    }
+
+    pub fn evaluate(&mut self, input: &str) -> Result<EvalResult, Ts2gError> {
+        let start = Instant::now();
+        let statements = parse(input)?;
+        let parse_duration = start.elapsed();
+
+        self.printed = Some(Vec::new());
+        let start = Instant::now();
+        let res = self.run(statements);
+        let eval_duration = start.elapsed();
+        let printed = self.printed.take().unwrap_or_default();
+        res.map_err(|e| Ts2gError::runtime(e, input, self.error_span))?;
+
+        Ok(EvalResult {
+            printed,
+            vars: self.scopes[0]
+                .vars
+                .iter()
+                .map(|(id, v)| (id.clone(), v.clone()))
+                .collect(),
+            parse_duration,
+            eval_duration,
+            warnings: self.warnings.clone(),
+        })
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The span of the innermost statement the last failed `run` stopped in. Errors raised before
+    /// any statement starts, like the nesting check, have none.
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_span
+    }
+
+    pub fn run(&mut self, statements: Vec<Box<Spanned<Statement>>>) -> Result<(), InterpError> {
+        self.steps = 0;
+        self.error_span = None;
+        // A budget too large to add to the clock is as good as none.
+        self.deadline = self
+            .time_budget
+            .and_then(|budget| Instant::now().checked_add(budget));
+        // Refusing too-deep input up front also means it is dropped from here, not from deep
+        // inside the tree walk, where even dropping it could exhaust the stack.
+        if nesting(&statements) > self.max_depth {
+            return Err(InterpError::RecursionLimitExceeded(self.max_depth));
+        }
+        self.warnings = warnings::check(&statements);
+        for statement in &statements {
+            // A failed statement may leave operands behind; drop them so the interpreter stays usable.
+            if let Err(e) = self.visit_statement(statement) {
+                self.stack.clear();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<(), InterpError> {
+        self.steps += 1;
+        if let Some(max) = self.max_steps
+            && self.steps > max
+        {
+            return Err(InterpError::StepLimitExceeded(max));
+        }
+        if self.steps.is_multiple_of(STEPS_PER_CLOCK_CHECK)
+            && let (Some(deadline), Some(budget)) = (self.deadline, self.time_budget)
+            && Instant::now() >= deadline
+        {
+            return Err(InterpError::TimeLimitExceeded(budget));
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Object, InterpError> {
+        self.stack.pop().ok_or(InterpError::StackUnderflow)
+    }
+    fn peek(&self) -> Result<&Object, InterpError> {
+        self.stack.last().ok_or(InterpError::StackUnderflow)
+    }
+
+    fn pop_number(&mut self) -> Result<Value, InterpError> {
+        match self.pop()? {
+            Object::Number(v) => Ok(v),
+            o => Err(InterpError::ExpectedType(
+                "a number".to_owned(),
+                o.type_name(),
+            )),
+        }
+    }
+    fn pop_array(&mut self) -> Result<Vec<Value>, InterpError> {
+        match self.pop()? {
+            Object::Array(values) => Ok(values),
+            o => Err(InterpError::ExpectedType("array".to_owned(), o.type_name())),
+        }
+    }
+    fn pop_bool(&mut self) -> Result<bool, InterpError> {
+        match self.pop()? {
+            Object::Bool(b) => Ok(b),
+            o => Err(InterpError::ExpectedType("bool".to_owned(), o.type_name())),
+        }
+    }
+
+    // Every visit counts towards the depth limit, so deeply nested input fails cleanly instead of
+    // overflowing the native stack.
+    fn enter(&mut self) -> Result<(), InterpError> {
+        if self.depth >= self.max_depth {
+            return Err(InterpError::RecursionLimitExceeded(self.max_depth));
         }
+        self.depth += 1;
+        Ok(())
     }
 
-    pub fn visit_statement(&mut self, statement: Box<Statement>) {
-        match *statement {
-            Statement::ExprStatement(expr) => {
-                self.visit_expr(expr);
-                self.stack.pop().unwrap();
+    pub fn visit_statement(&mut self, statement: &Spanned<Statement>) -> Result<(), InterpError> {
+        self.enter()?;
+        let Spanned { node, span } = statement;
+        let height = self.stack.len();
+        let res = self.eval_statement(node);
+        self.depth -= 1;
+        // Every statement, nested or not, consumes exactly what its expressions push.
+        debug_assert!(
+            res.is_err() || self.stack.len() == height,
+            "values left on the stack: {:?}",
+            &self.stack[height.min(self.stack.len())..]
+        );
+        // The innermost statement fails first; the ones enclosing it keep its span.
+        if res.is_err() && self.error_span.is_none() {
+            self.error_span = Some(*span);
+        }
+        res
+    }
+    fn eval_statement(&mut self, statement: &Statement) -> Result<(), InterpError> {
+        self.step()?;
+        match statement {
+            Statement::ExprStatement(expr) => match &**expr {
+                // A void call pushes nothing, so there is nothing to discard.
+                Expr::Call(name, args) => {
+                    self.visit_call(name, args)?;
+                }
+                // An assignment statement only stores; its value is never pushed to be dropped.
+                Expr::Eq(id, expr) => self.visit_assign(id, expr)?,
+                expr => {
+                    self.visit_expr(expr)?;
+                    self.pop()?;
+                }
+            },
+            // Each declarator is bound before the next is evaluated, so later ones can use it.
+            Statement::Let(declarators) => {
+                for (id, t, expr) in declarators {
+                    let Some(expr) = expr else {
+                        self.declare_unassigned(id.clone(), t.clone())?;
+                        continue;
+                    };
+                    let lit = Self::literal(expr);
+                    self.visit_expr(expr)?;
+                    let res = self.pop()?;
+                    let res = self.annotate(id, t.as_deref(), res, lit)?;
+                    self.declare(id.clone(), t.clone(), res, false)?;
+                }
+            }
+            // Either way every value is evaluated before any of the names is bound.
+            Statement::Destructure(ids, expr) => {
+                let values = match &**expr {
+                    // A tuple's elements are evaluated one by one, so unlike an array's they may
+                    // differ in type.
+                    Expr::Tuple(elements) => {
+                        let mut values = Vec::new();
+                        for element in elements {
+                            self.visit_expr(element)?;
+                            values.push(self.pop()?);
+                        }
+                        values
+                    }
+                    expr => {
+                        self.visit_expr(expr)?;
+                        let values = self.pop_array()?;
+                        values.into_iter().map(Object::Number).collect()
+                    }
+                };
+                if values.len() != ids.len() {
+                    return Err(InterpError::DestructureMismatch(ids.len(), values.len()));
+                }
+                for (id, v) in ids.iter().zip(values) {
+                    self.declare(id.clone(), None, v, false)?;
+                }
             }
-            Statement::Let(id, _t, expr) => {
-                self.visit_expr(expr);
-                let res = self.stack.pop().unwrap();
-                self.vars.insert(id, res);
+            Statement::Const(id, t, expr) => {
+                let lit = Self::literal(expr);
+                self.visit_expr(expr)?;
+                let res = self.pop()?;
+                let res = self.annotate(id, Some(t), res, lit)?;
+                self.declare(id.clone(), Some(t.clone()), res, true)?;
             }
-            Statement::Print(expr) => {
-                self.visit_expr(expr);
-                println!("{}", self.stack.pop().unwrap());
+            Statement::Block(statements) => {
+                self.scopes.push(Scope::default());
+                let res = statements
+                    .iter()
+                    .try_for_each(|statement| self.visit_statement(statement));
+                self.scopes.pop();
+                res?;
+            }
+            // The loop gets a scope for its init clause and each iteration a fresh one for the body.
+            Statement::For(init, cond, step, body) => {
+                self.scopes.push(Scope::default());
+                let res = self.visit_for(init.as_deref(), cond.as_deref(), step.as_deref(), body);
+                self.scopes.pop();
+                res?;
+            }
+            Statement::DoWhile(body, cond) => self.visit_do_while(body, cond)?,
+            // Every argument is evaluated before anything is printed, so a failing argument
+            // prints nothing.
+            Statement::Print(args) => {
+                let mut line = String::new();
+                for (i, arg) in args.iter().enumerate() {
+                    self.visit_expr(arg)?;
+                    let v = self.pop()?;
+                    if i > 0 {
+                        line.push(' ');
+                    }
+                    match (self.float_precision, self.scientific) {
+                        (Some(digits), true) => line.push_str(&format!("{:#.*}", digits, v)),
+                        (Some(digits), false) => line.push_str(&format!("{:.*}", digits, v)),
+                        (None, true) => line.push_str(&format!("{:#}", v)),
+                        (None, false) => line.push_str(&v.to_string()),
+                    }
+                }
+                println!("{}", line);
+                if let Some(printed) = &mut self.printed {
+                    printed.push(line);
+                }
             }
         }
+        Ok(())
     }
-    pub fn visit_expr(&mut self, expr: Box<Expr>) {
-        match *expr {
+    pub fn visit_expr(&mut self, expr: &Expr) -> Result<(), InterpError> {
+        self.enter()?;
+        let res = self.eval_expr(expr);
+        self.depth -= 1;
+        res
+    }
+    fn eval_expr(&mut self, expr: &Expr) -> Result<(), InterpError> {
+        self.step()?;
+        match expr {
+            Expr::Str(s) => self.stack.push(Object::Str(s.clone())),
+            Expr::Bool(b) => self.stack.push(Object::Bool(*b)),
             Expr::Number(n) => {
-                self.stack.push(Value {
-                    v: Number { f64: n },
+                self.stack.push(Object::Number(Value {
+                    v: Number { f64: n.as_f64() },
                     t: NumType::F64,
-                });
+                }));
             }
             Expr::Id(id) => {
-                self.stack.push(*self.vars.get(&id).unwrap());
+                let v = self.lookup(id)?.clone();
+                self.stack.push(v);
             }
             Expr::PI => {
-                self.stack.push(Value {
+                self.stack.push(Object::Number(Value {
                     v: Number {
                         f64: f64::consts::PI,
                     },
                     t: NumType::F64,
-                });
+                }));
             }
             Expr::E => {
-                self.stack.push(Value {
+                self.stack.push(Object::Number(Value {
                     v: Number {
                         f64: f64::consts::E,
                     },
                     t: NumType::F64,
-                });
+                }));
+            }
+            Expr::Constant(c) => {
+                self.stack.push(Object::Number(Value {
+                    v: Number { f64: c.value() },
+                    t: NumType::F64,
+                }));
+            }
+            Expr::Call(name, args) => {
+                let result = self.visit_call(name, args)?;
+                self.stack
+                    .push(result.ok_or_else(|| InterpError::VoidValue(name.clone()))?);
+            }
+            // Interpolated values are shown as plain Display would, without print's precision.
+            Expr::Template(parts) => {
+                let mut s = String::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Str(text) => s.push_str(text),
+                        TemplatePart::Expr(expr) => {
+                            self.visit_expr(expr)?;
+                            s.push_str(&self.pop()?.to_string());
+                        }
+                    }
+                }
+                self.stack.push(Object::Str(s));
+            }
+            Expr::Method(receiver, name, args) => {
+                self.visit_expr(receiver)?;
+                let receiver = self.pop()?;
+                let mut values = Vec::new();
+                for arg in args {
+                    self.visit_expr(arg)?;
+                    values.push(self.pop()?);
+                }
+                self.stack.push(builtins::method(receiver, name, values)?);
             }
-            Expr::Parenthesis(expr) => self.visit_expr(expr),
+            Expr::Array(elements) => {
+                let array = self.visit_array(elements)?;
+                self.stack.push(array);
+            }
+            Expr::Tuple(_) => return Err(InterpError::MisplacedTuple),
+            Expr::Index(array, index) => {
+                let index_lit = Self::literal(index);
+                self.visit_expr(array)?;
+                self.visit_expr(index)?;
+                let index = self.pop_number()?;
+                let index = Self::literal_as(index_lit, NumType::Usize)
+                    .unwrap_or(index)
+                    .integer()?;
+                let values = self.pop_array()?;
+                let v = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| values.get(i))
+                    .ok_or(InterpError::IndexOutOfBounds(index, values.len()))?;
+                self.stack.push(Object::Number(*v));
+            }
+            // Strings count chars rather than bytes, so "héllo".length is 5.
+            Expr::Length(array) => {
+                self.visit_expr(array)?;
+                let len = match self.pop()? {
+                    Object::Array(values) => values.len(),
+                    Object::Str(s) => s.chars().count(),
+                    o => {
+                        return Err(InterpError::ExpectedType(
+                            "an array or string".to_owned(),
+                            o.type_name(),
+                        ));
+                    }
+                };
+                self.stack.push(Object::Number(Value::from_i128(
+                    len as i128,
+                    NumType::Usize,
+                )));
+            }
+            Expr::PreIncrement(id) => self.visit_step(id, true, true)?,
+            Expr::PreDecrement(id) => self.visit_step(id, false, true)?,
+            Expr::PostIncrement(id) => self.visit_step(id, true, false)?,
+            Expr::PostDecrement(id) => self.visit_step(id, false, false)?,
+            Expr::Parenthesis(expr) => self.visit_expr(expr)?,
             Expr::Exponent(l, r) => {
-                self.visit_expr(l);
-                self.visit_expr(r);
-                let r = self.stack.pop().unwrap();
-                let l = self.stack.pop().unwrap();
-                self.stack.push(l.powf(r));
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack.push(Object::Number(l.pow(r, self.overflow)?));
             }
             Expr::Multiply(l, r) => {
-                self.visit_expr(l);
-                self.visit_expr(r);
-                let r = self.stack.pop().unwrap();
-                let l = self.stack.pop().unwrap();
-                self.stack.push(l * r);
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack
+                    .push(Object::Number(l.arith(r, Arith::Mul, self.overflow)?));
             }
             Expr::Divide(l, r) => {
-                self.visit_expr(l);
-                self.visit_expr(r);
-                let r = self.stack.pop().unwrap();
-                let l = self.stack.pop().unwrap();
-                self.stack.push(l / r);
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack
+                    .push(Object::Number(l.arith(r, Arith::Div, self.overflow)?));
             }
             Expr::Add(l, r) => {
-                self.visit_expr(l);
-                self.visit_expr(r);
-                let r = self.stack.pop().unwrap();
-                let l = self.stack.pop().unwrap();
-                self.stack.push(l + r);
+                let (l_lit, r_lit) = (Self::literal(l), Self::literal(r));
+                self.visit_expr(l)?;
+                self.visit_expr(r)?;
+                let r = self.pop()?;
+                let l = self.pop()?;
+                let sum = match (l, r) {
+                    (Object::Number(l), Object::Number(r)) => {
+                        let (l, r) = self.coerce(l, r, l_lit, r_lit)?;
+                        Object::Number(l.arith(r, Arith::Add, self.overflow)?)
+                    }
+                    (Object::Str(l), Object::Str(r)) => Object::Str(l + &r),
+                    (l, r) => return Err(InterpError::TypeMismatch(l.type_name(), r.type_name())),
+                };
+                self.stack.push(sum);
             }
             Expr::Sub(l, r) => {
-                self.visit_expr(l);
-                self.visit_expr(r);
-                let r = self.stack.pop().unwrap();
-                let l = self.stack.pop().unwrap();
-                self.stack.push(l - r);
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack
+                    .push(Object::Number(l.arith(r, Arith::Sub, self.overflow)?));
+            }
+            Expr::Saturate(expr, t) => {
+                self.visit_expr(expr)?;
+                let v = self.pop_number()?;
+                self.stack.push(Object::Number(v.saturate(*t)));
+            }
+            Expr::Cast(expr, t) => {
+                // A whole literal converts like a Rust integer, so `-1 as u8` wraps to 255 where
+                // the f64 it would otherwise be saturates to 0.
+                let v = match Self::whole_literal(expr) {
+                    Some(v) => v,
+                    None => {
+                        self.visit_expr(expr)?;
+                        self.pop_number()?
+                    }
+                };
+                self.stack.push(Object::Number(v.cast(*t)));
+            }
+            Expr::Not(expr) => {
+                self.visit_expr(expr)?;
+                let b = self.pop_bool()?;
+                self.stack.push(Object::Bool(!b));
+            }
+            Expr::TypeOf(expr) => {
+                self.visit_expr(expr)?;
+                let o = self.pop()?;
+                self.stack.push(Object::Str(o.type_name()));
+            }
+            Expr::Neg(expr) => {
+                self.visit_expr(expr)?;
+                let v = self.pop_number()?;
+                self.stack
+                    .push(Object::Number(v.neg_with_policy(self.overflow)?));
+            }
+            Expr::BitNot(expr) => {
+                self.visit_expr(expr)?;
+                let v = self.pop_number()?;
+                self.stack.push(Object::Number(v.bitnot()?));
+            }
+            Expr::Shl(l, r) => self.visit_shift(l, r, true)?,
+            Expr::Shr(l, r) => self.visit_shift(l, r, false)?,
+            Expr::BitAnd(l, r) => {
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack.push(Object::Number(l.bitwise(r, |a, b| a & b)?));
+            }
+            Expr::BitXor(l, r) => {
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack.push(Object::Number(l.bitwise(r, |a, b| a ^ b)?));
+            }
+            Expr::BitOr(l, r) => {
+                let (l, r) = self.visit_numbers(l, r)?;
+                self.stack.push(Object::Number(l.bitwise(r, |a, b| a | b)?));
+            }
+            Expr::Less(l, r) => self.visit_compare(l, r, Ordering::is_lt)?,
+            Expr::LessEq(l, r) => self.visit_compare(l, r, Ordering::is_le)?,
+            Expr::Greater(l, r) => self.visit_compare(l, r, Ordering::is_gt)?,
+            Expr::GreaterEq(l, r) => self.visit_compare(l, r, Ordering::is_ge)?,
+            Expr::Equal(l, r) => {
+                let eq = self.visit_equality(l, r)?;
+                self.stack.push(Object::Bool(eq));
+            }
+            Expr::NotEqual(l, r) => {
+                let eq = self.visit_equality(l, r)?;
+                self.stack.push(Object::Bool(!eq));
+            }
+            // && and || only evaluate the right operand when the left doesn't decide the result.
+            Expr::And(l, r) => {
+                self.visit_expr(l)?;
+                if self.pop_bool()? {
+                    self.visit_expr(r)?;
+                    let r = self.pop_bool()?;
+                    self.stack.push(Object::Bool(r));
+                } else {
+                    self.stack.push(Object::Bool(false));
+                }
+            }
+            Expr::Or(l, r) => {
+                self.visit_expr(l)?;
+                if self.pop_bool()? {
+                    self.stack.push(Object::Bool(true));
+                } else {
+                    self.visit_expr(r)?;
+                    let r = self.pop_bool()?;
+                    self.stack.push(Object::Bool(r));
+                }
+            }
+            Expr::Ternary(c, a, b) => {
+                self.visit_expr(c)?;
+                let (taken, untaken) = if self.pop_bool()? { (a, b) } else { (b, a) };
+                self.visit_expr(taken)?;
+                let taken_type = self.peek()?.type_name();
+                if let Some(untaken_type) = self.expr_type(untaken)
+                    && taken_type != untaken_type
+                {
+                    return Err(InterpError::TypeMismatch(taken_type, untaken_type));
+                }
             }
             Expr::Eq(id, expr) => {
-                self.visit_expr(expr);
-                let v = self.stack.last().unwrap();
-                self.vars.insert(id, *v).unwrap();
+                self.visit_assign(id, expr)?;
+                // The expression's value is the variable's new value, after any typing.
+                let v = self.lookup(id)?.clone();
+                self.stack.push(v);
+            }
+        }
+        Ok(())
+    }
+    // Best-effort type of an expression without evaluating it, for checks on branches that don't run.
+    fn expr_type(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Number(_) | Expr::PI | Expr::E | Expr::Constant(_) => {
+                Some(NumType::F64.to_string())
+            }
+            Expr::Str(_) | Expr::TypeOf(_) => Some("string".to_owned()),
+            Expr::Array(_) => Some("array".to_owned()),
+            Expr::Length(_) => Some(NumType::Usize.to_string()),
+            Expr::Method(_, _, _) | Expr::Template(_) => Some("string".to_owned()),
+            Expr::Bool(_) => Some("bool".to_owned()),
+            Expr::Id(id)
+            | Expr::PreIncrement(id)
+            | Expr::PreDecrement(id)
+            | Expr::PostIncrement(id)
+            | Expr::PostDecrement(id) => self.lookup(id).ok().map(Object::type_name),
+            Expr::Parenthesis(expr) | Expr::Eq(_, expr) => self.expr_type(expr),
+            Expr::Cast(_, t) | Expr::Saturate(_, t) => Some(t.to_string()),
+            Expr::Neg(expr) | Expr::BitNot(expr) | Expr::Shl(expr, _) | Expr::Shr(expr, _) => {
+                self.expr_type(expr)
+            }
+            Expr::Exponent(l, r)
+            | Expr::Multiply(l, r)
+            | Expr::Divide(l, r)
+            | Expr::Add(l, r)
+            | Expr::Sub(l, r)
+            | Expr::BitAnd(l, r)
+            | Expr::BitXor(l, r)
+            | Expr::BitOr(l, r) => match (self.expr_type(l), self.expr_type(r)) {
+                (Some(l), Some(r)) if l == r => Some(l),
+                _ => None,
             },
+            Expr::Not(_)
+            | Expr::Less(_, _)
+            | Expr::LessEq(_, _)
+            | Expr::Greater(_, _)
+            | Expr::GreaterEq(_, _)
+            | Expr::Equal(_, _)
+            | Expr::NotEqual(_, _)
+            | Expr::And(_, _)
+            | Expr::Or(_, _) => Some("bool".to_owned()),
+            Expr::Ternary(_, a, b) => self.expr_type(a).or_else(|| self.expr_type(b)),
+            Expr::Call(_, _) | Expr::Index(_, _) | Expr::Tuple(_) => None,
+        }
+    }
+
+    fn visit_call(&mut self, name: &str, args: &[Box<Expr>]) -> Result<Option<Object>, InterpError> {
+        let mut values = Vec::new();
+        for arg in args {
+            let lit = Self::literal(arg);
+            self.visit_expr(arg)?;
+            values.push((lit, self.pop()?));
+        }
+        // Bare literals take the type of the first other number, or of an array's elements, so
+        // `max(x, 0)` and `includes(bytes, 2)` work for any integer type. A literal that doesn't
+        // fit keeps its f64 and fails the built-in's type check.
+        let t = values.iter().find_map(|(lit, v)| match v {
+            Object::Number(v) if lit.is_none() => Some(v.t),
+            Object::Array(elements) => elements.first().map(|v| v.t),
+            _ => None,
+        });
+        let values = values
+            .into_iter()
+            .map(|(lit, v)| match t.and_then(|t| Self::literal_as(lit, t)) {
+                Some(adopted) => Object::Number(adopted),
+                None => v,
+            })
+            .collect();
+        builtins::call(name, values, &mut self.rng)
+    }
+
+    // Bare literals take the type of the first other element, like operands of an operator.
+    fn visit_array(&mut self, elements: &[Box<Expr>]) -> Result<Object, InterpError> {
+        let mut values = Vec::new();
+        for element in elements {
+            let lit = Self::literal(element);
+            self.visit_expr(element)?;
+            values.push((lit, self.pop_number()?));
+        }
+        let t = values
+            .iter()
+            .find(|(lit, _)| lit.is_none())
+            .map_or(NumType::F64, |(_, v)| v.t);
+        values
+            .into_iter()
+            .map(|(lit, v)| {
+                let v = match lit {
+                    Some(lit) if t.is_integer() => Value::from_literal(lit, t)?,
+                    lit => Self::literal_as(lit, t).unwrap_or(v),
+                };
+                if v.t == t {
+                    Ok(v)
+                } else {
+                    Err(InterpError::TypeMismatch(t.to_string(), v.t.to_string()))
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Object::Array)
+    }
+
+    fn visit_for(
+        &mut self,
+        init: Option<&Spanned<Statement>>,
+        cond: Option<&Expr>,
+        step: Option<&Expr>,
+        body: &[Box<Spanned<Statement>>],
+    ) -> Result<(), InterpError> {
+        if let Some(init) = init {
+            self.visit_statement(init)?;
+        }
+        loop {
+            // Charge each iteration so even `for (;;) {}` runs into max_steps.
+            self.step()?;
+            if let Some(cond) = cond {
+                self.visit_expr(cond)?;
+                if !self.pop_bool()? {
+                    return Ok(());
+                }
+            }
+            self.scopes.push(Scope::default());
+            let res = body
+                .iter()
+                .try_for_each(|statement| self.visit_statement(statement));
+            self.scopes.pop();
+            res?;
+            if let Some(step) = step {
+                self.visit_expr(step)?;
+                self.pop()?;
+            }
+        }
+    }
+
+    // Like visit_for, each iteration is charged a step and gets its own scope, which is gone again
+    // by the time the condition is checked.
+    fn visit_do_while(
+        &mut self,
+        body: &[Box<Spanned<Statement>>],
+        cond: &Expr,
+    ) -> Result<(), InterpError> {
+        loop {
+            self.step()?;
+            self.scopes.push(Scope::default());
+            let res = body
+                .iter()
+                .try_for_each(|statement| self.visit_statement(statement));
+            self.scopes.pop();
+            res?;
+            self.visit_expr(cond)?;
+            if !self.pop_bool()? {
+                return Ok(());
+            }
+        }
+    }
+
+    // Without an annotation a binding takes whatever it is given. A bare literal must fit the
+    // declared type; any other number is cast to it.
+    fn annotate(
+        &self,
+        id: &str,
+        t: Option<&str>,
+        v: Object,
+        lit: Option<SignedLiteral>,
+    ) -> Result<Object, InterpError> {
+        match (t, v) {
+            (None, v) => Ok(v),
+            (Some("string"), v @ Object::Str(_)) | (Some("bool"), v @ Object::Bool(_)) => Ok(v),
+            (Some(t), Object::Number(n)) if t.parse::<NumType>().is_ok() => {
+                let t = t.parse().unwrap();
+                Ok(Object::Number(match lit {
+                    Some(lit) => Value::from_literal(lit, t)?,
+                    None => n.cast(t),
+                }))
+            }
+            (Some(t), o) => Err(InterpError::InvalidArgument(format!(
+                "Cannot store a {} value in {} of type {}.",
+                o.type_name(),
+                id,
+                t
+            ))),
+        }
+    }
+
+    fn lookup(&self, id: &str) -> Result<&Object, InterpError> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(v) = scope.vars.get(id) {
+                return Ok(v);
+            }
+            if scope.unassigned.contains(id) {
+                return Err(InterpError::UsedBeforeAssignment(id.to_owned()));
+            }
+        }
+        Err(InterpError::Undefined(id.to_owned()))
+    }
+
+    fn declare(
+        &mut self,
+        id: String,
+        t: Option<String>,
+        v: Object,
+        constant: bool,
+    ) -> Result<(), InterpError> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.consts.contains(&id) {
+            return Err(InterpError::ConstRedeclared(id));
+        }
+        if constant {
+            scope.consts.insert(id.clone());
+        }
+        scope.unassigned.remove(&id);
+        scope.set_type(&id, t);
+        scope.vars.insert(id, v);
+        Ok(())
+    }
+
+    fn declare_unassigned(&mut self, id: String, t: Option<String>) -> Result<(), InterpError> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.consts.contains(&id) {
+            return Err(InterpError::ConstRedeclared(id));
+        }
+        scope.vars.remove(&id);
+        scope.set_type(&id, t);
+        scope.unassigned.insert(id);
+        Ok(())
+    }
+
+    // Evaluates `id = expr` without pushing anything.
+    fn visit_assign(&mut self, id: &str, expr: &Expr) -> Result<(), InterpError> {
+        let lit = Self::literal(expr);
+        self.visit_expr(expr)?;
+        let v = self.pop()?;
+        self.assign(id, v, lit)
+    }
+
+    // Every assignment, the first included, is checked against the declared type the same way
+    // an initializer is.
+    fn assign(&mut self, id: &str, v: Object, lit: Option<SignedLiteral>) -> Result<(), InterpError> {
+        let index = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.vars.contains_key(id) || scope.unassigned.contains(id))
+            .ok_or_else(|| InterpError::Undefined(id.to_owned()))?;
+        let scope = &self.scopes[index];
+        if scope.consts.contains(id) {
+            return Err(InterpError::AssignToConst(id.to_owned()));
+        }
+        let v = self.annotate(id, scope.types.get(id).map(String::as_str), v, lit)?;
+        let scope = &mut self.scopes[index];
+        scope.unassigned.remove(id);
+        scope.vars.insert(id.to_owned(), v);
+        Ok(())
+    }
+
+    // ++/--: prefix forms yield the updated value, postfix forms the original one.
+    fn visit_step(&mut self, id: &str, increment: bool, prefix: bool) -> Result<(), InterpError> {
+        let old = match self.lookup(id)? {
+            Object::Number(v) => *v,
+            o => {
+                return Err(InterpError::ExpectedType(
+                    "a number".to_owned(),
+                    o.type_name(),
+                ));
+            }
+        };
+        let one = Value::one(old.t);
+        let new = if increment {
+            old.arith(one, Arith::Add, self.overflow)?
+        } else {
+            old.arith(one, Arith::Sub, self.overflow)?
+        };
+        self.assign(id, Object::Number(new), None)?;
+        self.stack
+            .push(Object::Number(if prefix { new } else { old }));
+        Ok(())
+    }
+
+    // Evaluates both operands of an arithmetic operator and brings them to a common type.
+    fn visit_numbers(&mut self, l: &Expr, r: &Expr) -> Result<(Value, Value), InterpError> {
+        let (l_lit, r_lit) = (Self::literal(l), Self::literal(r));
+        self.visit_expr(l)?;
+        self.visit_expr(r)?;
+        let r = self.pop_number()?;
+        let l = self.pop_number()?;
+        self.coerce(l, r, l_lit, r_lit)
+    }
+    // The one place binary operators settle their operands' types. Bare literals adopt the other
+    // operand's type in either mode; what remains must match unless coercion is Loose.
+    fn coerce(
+        &self,
+        l: Value,
+        r: Value,
+        l_lit: Option<SignedLiteral>,
+        r_lit: Option<SignedLiteral>,
+    ) -> Result<(Value, Value), InterpError> {
+        let r = Self::literal_as(r_lit, l.t).unwrap_or(r);
+        let l = Self::literal_as(l_lit, r.t).unwrap_or(l);
+        match self.coercion {
+            CoercionMode::Loose => Value::coerce_pair(l, r),
+            CoercionMode::Strict if l.t == r.t => Ok((l, r)),
+            CoercionMode::Strict => {
+                Err(InterpError::TypeMismatch(l.t.to_string(), r.t.to_string()))
+            }
+        }
+    }
+    // A literal next to a typed operand takes that operand's type when it fits, so `x & 3` or
+    // `x + 1` stay in x's type instead of widening to f64, and `y * 0.5` stays f32.
+    fn literal(expr: &Expr) -> Option<SignedLiteral> {
+        match expr {
+            Expr::Number(literal) => Some(SignedLiteral {
+                negative: false,
+                literal: *literal,
+            }),
+            // `-5` is parsed as a negation, but is still a literal as far as typing goes.
+            Expr::Neg(expr) => Self::literal(expr).map(|lit| SignedLiteral {
+                negative: !lit.negative,
+                ..lit
+            }),
+            // Parentheses don't change what a literal is, so stripping them changes nothing.
+            Expr::Parenthesis(expr) => Self::literal(expr),
+            _ => None,
+        }
+    }
+    // The exact value of a whole literal with any signs and `~` in front of it, as an i128, or a
+    // u128 past i128's range.
+    fn whole_literal(expr: &Expr) -> Option<Value> {
+        if let Some(lit) = Self::literal(expr) {
+            return match lit.literal {
+                Literal::Int(_) => Value::from_literal(lit, NumType::I128)
+                    .or_else(|_| Value::from_literal(lit, NumType::U128))
+                    .ok(),
+                Literal::Float(_) => None,
+            };
+        }
+        match expr {
+            Expr::BitNot(expr) => Self::whole_literal(expr)?.bitnot().ok(),
+            Expr::Neg(expr) => Self::whole_literal(expr)?.checked_neg().ok(),
+            Expr::Parenthesis(expr) => Self::whole_literal(expr),
+            _ => None,
+        }
+    }
+    fn literal_as(n: Option<SignedLiteral>, t: NumType) -> Option<Value> {
+        let n = n?;
+        // Past f32's range a literal stays f64 rather than becoming infinity.
+        if t == NumType::F32 && (n.as_f64() as f32).is_infinite() {
+            return None;
+        }
+        Value::from_literal(n, t).ok()
+    }
+    fn visit_shift(&mut self, l: &Expr, r: &Expr, left: bool) -> Result<(), InterpError> {
+        let r_lit = Self::literal(r);
+        self.visit_expr(l)?;
+        self.visit_expr(r)?;
+        let amount = self.pop_number()?;
+        let v = self.pop_number()?;
+        let amount = Self::literal_as(r_lit, NumType::U32).unwrap_or(amount);
+        self.stack.push(Object::Number(v.shift(amount, left, self.overflow)?));
+        Ok(())
+    }
+    fn visit_compare(
+        &mut self,
+        l: &Expr,
+        r: &Expr,
+        test: fn(Ordering) -> bool,
+    ) -> Result<(), InterpError> {
+        let (l, r) = self.visit_numbers(l, r)?;
+        self.stack
+            .push(Object::Bool(l.partial_cmp(&r).is_some_and(test)));
+        Ok(())
+    }
+    fn visit_equality(&mut self, l: &Expr, r: &Expr) -> Result<bool, InterpError> {
+        let (l_lit, r_lit) = (Self::literal(l), Self::literal(r));
+        self.visit_expr(l)?;
+        self.visit_expr(r)?;
+        let r = self.pop()?;
+        let l = self.pop()?;
+        match (l, r) {
+            (Object::Number(l), Object::Number(r)) => {
+                let (l, r) = self.coerce(l, r, l_lit, r_lit)?;
+                Ok(l == r)
+            }
+            (Object::Bool(l), Object::Bool(r)) => Ok(l == r),
+            (Object::Str(l), Object::Str(r)) => Ok(l == r),
+            (Object::Array(l), Object::Array(r)) => {
+                if l.len() != r.len() {
+                    return Ok(false);
+                }
+                for (l, r) in l.into_iter().zip(r) {
+                    let (l, r) = self.coerce(l, r, None, None)?;
+                    if l != r {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (l, r) => Err(InterpError::TypeMismatch(l.type_name(), r.type_name())),
+        }
+    }
+}
+
+// How deeply statements and expressions nest, counted the way `enter` counts visits. Measured
+// with an explicit stack, so it is safe on input of any depth.
+fn nesting(statements: &[Box<Spanned<Statement>>]) -> usize {
+    let mut pending: Vec<_> = statements.iter().map(|s| (Node::Statement(s), 1)).collect();
+    let mut deepest = 0;
+    while let Some((node, depth)) = pending.pop() {
+        deepest = deepest.max(depth);
+        let children = match node {
+            Node::Statement(statement) => statement.children(),
+            Node::Expr(expr) => expr.children().into_iter().map(Node::Expr).collect(),
+        };
+        pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+    }
+    deepest
+}
+
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub col: usize,
+    pub msg: String,
+}
+impl SyntaxError {
+    fn at(input: &str, location: usize, msg: String) -> Self {
+        let (line, col) = line_col(input, location);
+        Self { line, col, msg }
+    }
+}
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "error at line {}, col {}: {}",
+            self.line, self.col, self.msg
+        )
+    }
+}
+impl Error for SyntaxError {}
+
+// Converts a byte offset into the 1-based line and column it falls on.
+fn line_col(input: &str, location: usize) -> (usize, usize) {
+    let before = &input[..location.min(input.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+// Everything that can stop a program, so callers can match on the kind of failure.
+#[derive(Debug)]
+pub enum Ts2gError {
+    Parse(SyntaxError),
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    Runtime(InterpError),
+    // A runtime error, with where the statement it stopped in starts.
+    RuntimeAt {
+        line: usize,
+        col: usize,
+        error: InterpError,
+    },
+    // Bad command-line arguments.
+    Usage(String),
+    // How many programs of a batch failed, out of how many. Each failure was already reported.
+    Batch {
+        failed: usize,
+        total: usize,
+    },
+}
+impl Display for Ts2gError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ts2gError::Parse(e) => write!(f, "{}", e),
+            Ts2gError::Io { path, source } => write!(f, "cannot read {}: {}", path, source),
+            Ts2gError::Runtime(e) => write!(f, "{}", e),
+            Ts2gError::RuntimeAt { line, col, error } => {
+                write!(f, "error at line {}, col {}: {}", line, col, error)
+            }
+            Ts2gError::Usage(msg) => write!(f, "{}", msg),
+            Ts2gError::Batch { failed, total } => {
+                write!(f, "{} of {} programs failed", failed, total)
+            }
+        }
+    }
+}
+impl Error for Ts2gError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Ts2gError::Parse(e) => Some(e),
+            Ts2gError::Io { source, .. } => Some(source),
+            Ts2gError::Runtime(e) | Ts2gError::RuntimeAt { error: e, .. } => Some(e),
+            Ts2gError::Usage(_) | Ts2gError::Batch { .. } => None,
+        }
+    }
+}
+impl From<SyntaxError> for Ts2gError {
+    fn from(e: SyntaxError) -> Self {
+        Ts2gError::Parse(e)
+    }
+}
+impl From<InterpError> for Ts2gError {
+    fn from(e: InterpError) -> Self {
+        Ts2gError::Runtime(e)
+    }
+}
+impl Ts2gError {
+    // Attaches the position of `span` in `source`, when there is one.
+    fn runtime(error: InterpError, source: &str, span: Option<Span>) -> Self {
+        match span {
+            Some(span) => {
+                let (line, col) = line_col(source, span.start);
+                Ts2gError::RuntimeAt { line, col, error }
+            }
+            None => Ts2gError::Runtime(error),
+        }
+    }
+}
+
+// Statement spans are byte ranges of `input` as given, not of the text with semicolons inserted.
+pub fn parse(input: &str) -> Result<Vec<Box<Spanned<Statement>>>, SyntaxError> {
+    let (input, inserted) = &lexer::insert_semicolons(input);
+    if let Some(at) = lexer::nested_template(input) {
+        let msg = "backtick inside ${...}: template literals can't nest".to_owned();
+        return Err(SyntaxError::at(input, at, msg));
+    }
+    let mut statements = SParser::new().parse(input).map_err(|e| {
+        let (location, msg) = match e {
+            ParseError::InvalidToken { location } => {
+                let c = input[location..].chars().next().unwrap_or_default();
+                (location, format!("unexpected '{}'", c))
+            }
+            ParseError::UnrecognizedEof { location, .. } => {
+                (location, "unexpected end of input".to_owned())
+            }
+            ParseError::UnrecognizedToken {
+                token: (start, token, _),
+                ..
+            }
+            | ParseError::ExtraToken {
+                token: (start, token, _),
+            } => (start, format!("unexpected '{}'", token.1)),
+            ParseError::User {
+                error: (location, msg),
+            } => (location, msg.to_owned()),
+        };
+        SyntaxError::at(input, location, msg)
+    })?;
+    if !inserted.is_empty() {
+        unshift_spans(&mut statements, inserted);
+    }
+    Ok(statements)
+}
+
+// Moves spans from the text the parser saw back onto the source, dropping the semicolons
+// inserted before each offset. Iterative, like `nesting`, since this runs before the depth check.
+fn unshift_spans(statements: &mut [Box<Spanned<Statement>>], inserted: &[usize]) {
+    let original = |at: usize| at - inserted.partition_point(|&i| i < at);
+    let mut pending: Vec<&mut Spanned<Statement>> =
+        statements.iter_mut().map(|s| &mut **s).collect();
+    while let Some(Spanned { node, span }) = pending.pop() {
+        *span = Span {
+            start: original(span.start),
+            end: original(span.end),
+        };
+        match node {
+            Statement::Block(body) | Statement::DoWhile(body, _) => {
+                pending.extend(body.iter_mut().map(|s| &mut **s));
+            }
+            Statement::For(init, _, _, body) => {
+                pending.extend(init.iter_mut().chain(body.iter_mut()).map(|s| &mut **s));
+            }
+            _ => {}
+        }
+    }
+}
+
+enum Input {
+    Stdin,
+    File(String),
+    Code(String),
+}
+
+// Usage: typescript2 [bench N] [--emit-ast] [--time-budget MS] [FILE | -c CODE]. Without a file
+// or -c the program is read from stdin. Input with `---` lines holds several programs, which are
+// run one after another as a batch.
+struct Options {
+    input: Input,
+    // Print the parsed AST as JSON instead of running the program.
+    emit_ast: bool,
+    // Run the program this many times and report timings.
+    bench: Option<u32>,
+    // Wall-clock limit on each run.
+    time_budget: Option<Duration>,
+}
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Ts2gError> {
+        let mut options = Options {
+            input: Input::Stdin,
+            emit_ast: false,
+            bench: None,
+            time_budget: None,
+        };
+        let mut args = args.peekable();
+        if args.next_if(|arg| arg == "bench").is_some() {
+            let iterations = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+            match iterations {
+                Some(n) => options.bench = Some(n),
+                None => {
+                    return Err(Ts2gError::Usage(
+                        "bench needs a positive number of runs".to_owned(),
+                    ));
+                }
+            }
+        }
+        while let Some(arg) = args.next() {
+            let input = match arg.as_str() {
+                "--emit-ast" => {
+                    options.emit_ast = true;
+                    continue;
+                }
+                "--time-budget" => {
+                    let ms = args.next().and_then(|ms| ms.parse().ok());
+                    match ms {
+                        Some(ms) => options.time_budget = Some(Duration::from_millis(ms)),
+                        None => {
+                            return Err(Ts2gError::Usage(
+                                "--time-budget needs a number of milliseconds".to_owned(),
+                            ));
+                        }
+                    }
+                    continue;
+                }
+                "-c" => match args.next() {
+                    Some(code) => Input::Code(code),
+                    None => return Err(Ts2gError::Usage("-c needs the code to run".to_owned())),
+                },
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(Ts2gError::Usage(format!("unknown option {}", flag)));
+                }
+                "-" => Input::Stdin,
+                _ => Input::File(arg),
+            };
+            if !matches!(options.input, Input::Stdin) {
+                return Err(Ts2gError::Usage("only one program can be given".to_owned()));
+            }
+            options.input = input;
+        }
+        Ok(options)
+    }
+
+    fn interpreter(&self) -> TS2G {
+        let mut builder = TS2G::builder();
+        if let Some(budget) = self.time_budget {
+            builder = builder.time_budget(budget);
+        }
+        builder.build()
+    }
+}
+
+fn read_source(input: Input) -> Result<String, Ts2gError> {
+    match input {
+        Input::File(path) => {
+            fs::read_to_string(&path).map_err(|source| Ts2gError::Io { path, source })
+        }
+        Input::Code(code) => Ok(code),
+        Input::Stdin => {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|source| Ts2gError::Io {
+                    path: "stdin".to_owned(),
+                    source,
+                })?;
+            Ok(source)
         }
     }
 }
 
-/*
-fn parse_statement<'a>(input: &'a str) -> Result<Box<Statement<'a>>, Box<dyn Error + 'a>> {
-    let parse = StatementParser::new();
-    match parse.parse(input) {
-        Ok(v) => Ok(v),
-        Err(e) => Err(Box::new(e.map_token(|e| Box::new(e)))),
+// A `#!` first line lets a script run as `./script.ts`. The newline is kept so reported line
+// numbers still match the file.
+fn strip_shebang(source: &str) -> &str {
+    if source.starts_with("#!") {
+        &source[source.find('\n').unwrap_or(source.len())..]
+    } else {
+        source
     }
 }
-*/
 
-/*
-fn parse_expr<'a>(input: &'a str) -> Result<Box<Expr<'a>>, Box<dyn Error + 'a>> {
-    let parse = ExprParser::new();
-    match parse.parse(input) {
-        Ok(v) => Ok(v),
-        Err(e) => Err(Box::new(e.map_token(|e| Box::new(e)))),
+// Runs an already parsed program repeatedly on one interpreter, reset between runs, and prints
+// min/mean/median/max evaluation times. Parsing isn't included.
+fn bench(
+    mut ts2builder: TS2G,
+    statements: &[Box<Spanned<Statement>>],
+    iterations: u32,
+) -> Result<(), Ts2gError> {
+    let mut times = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        ts2builder.reset();
+        let program = statements.to_vec();
+        let start = Instant::now();
+        ts2builder.run(program)?;
+        times.push(start.elapsed());
     }
+    times.sort();
+
+    let mid = times.len() / 2;
+    let median = if times.len() % 2 == 0 {
+        (times[mid - 1] + times[mid]) / 2
+    } else {
+        times[mid]
+    };
+    let mean = times.iter().sum::<Duration>() / iterations;
+    let ms = |d: Duration| d.as_nanos() as f64 / 1e6;
+    eprintln!(
+        "{:>8} {:>12} {:>12} {:>12} {:>12}",
+        "runs", "min ms", "mean ms", "median ms", "max ms"
+    );
+    eprintln!(
+        "{:>8} {:>12.3} {:>12.3} {:>12.3} {:>12.3}",
+        iterations,
+        ms(times[0]),
+        ms(mean),
+        ms(median),
+        ms(times[times.len() - 1])
+    );
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn emit_ast(statements: &[Box<Spanned<Statement>>]) -> Result<(), Ts2gError> {
+    use std::io::Write;
+
+    let io_error = |e: serde_json::Error| Ts2gError::Io {
+        path: "stdout".to_owned(),
+        source: e.into(),
+    };
+    let mut out = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, statements).map_err(io_error)?;
+    writeln!(out).map_err(|source| Ts2gError::Io {
+        path: "stdout".to_owned(),
+        source,
+    })
+}
+
+#[cfg(not(feature = "serde"))]
+fn emit_ast(_: &[Box<Spanned<Statement>>]) -> Result<(), Ts2gError> {
+    Err(Ts2gError::Usage(
+        "--emit-ast needs a build with the serde feature".to_owned(),
+    ))
 }
-*/
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let parser = SParser::new();
-    let mut sw = Stopwatch::start_new();
-    let statements = parser.parse("let x:u64=1+1;print(x);x=x+10;print(x);")?;
-    sw.stop();
-    println!("Parsed code in {}ms", sw.elapsed().as_micros() as f32 / 1000f32);
+// Splits input at lines that are just `---`. Input without any is a single program.
+fn split_programs(source: &str) -> Vec<&str> {
+    let mut programs = Vec::new();
+    let (mut start, mut offset) = (0, 0);
+    for line in source.split_inclusive('\n') {
+        if line.trim() == "---" {
+            programs.push(&source[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    programs.push(&source[start..]);
+    programs
+}
 
-    let mut ts2builder = TS2G::init();
+fn run_program(ts2builder: &mut TS2G, source: &str) -> Result<(), Ts2gError> {
+    let start = Instant::now();
+    let statements = parse(source)?;
+    eprintln!("Parsed code in {}ms", start.elapsed().as_micros() as f32 / 1000f32);
 
-    sw = Stopwatch::start_new();
-    for statement in statements {
-        ts2builder.visit_statement(statement);
+    let start = Instant::now();
+    let res = ts2builder.run(statements);
+    for warning in ts2builder.warnings() {
+        let (line, col) = line_col(source, warning.span.start);
+        eprintln!("warning at line {}, col {}: {}", line, col, warning.msg);
     }
-    sw.stop();
-    println!("Checked code in {}ms", sw.elapsed().as_micros() as f32 / 1000f32);
+    res.map_err(|e| Ts2gError::runtime(e, source, ts2builder.error_span()))?;
+    eprintln!("Checked code in {}ms", start.elapsed().as_micros() as f32 / 1000f32);
+    Ok(())
+}
 
+// Each program gets the interpreter freshly reset, and its output follows a `--- program N ---`
+// header. A program that fails to parse or run is reported and the rest still run. Line numbers
+// in its errors count from the program's own first line.
+fn run_batch(mut ts2builder: TS2G, programs: &[&str]) -> Result<(), Ts2gError> {
+    let mut failed = 0;
+    for (i, program) in programs.iter().enumerate() {
+        println!("--- program {} ---", i + 1);
+        ts2builder.reset();
+        if let Err(e) = run_program(&mut ts2builder, program) {
+            eprintln!("program {}: {}", i + 1, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(Ts2gError::Batch {
+            failed,
+            total: programs.len(),
+        });
+    }
     Ok(())
 }
+
+fn execute() -> Result<(), Ts2gError> {
+    let options = Options::parse(env::args().skip(1))?;
+    let mut ts2builder = options.interpreter();
+    let source = read_source(options.input)?;
+    let source = strip_shebang(&source);
+
+    // --emit-ast and bench only take a single program, so `---` there is just a syntax error.
+    if !options.emit_ast && options.bench.is_none() {
+        return match split_programs(source)[..] {
+            [program] => run_program(&mut ts2builder, program),
+            ref programs => run_batch(ts2builder, programs),
+        };
+    }
+    let start = Instant::now();
+    let statements = parse(source)?;
+    eprintln!("Parsed code in {}ms", start.elapsed().as_micros() as f32 / 1000f32);
+    match options.bench {
+        Some(iterations) if !options.emit_ast => bench(ts2builder, &statements, iterations),
+        _ => emit_ast(&statements),
+    }
+}
+
+fn main() {
+    if let Err(e) = execute() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::ast::{Expr, Spanned, Statement};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AstStats {
+    /// Statements and expressions visited.
+    pub node_count: usize,
+    /// Deepest expression nesting, where a lone literal has depth 1.
+    pub max_depth: usize,
+    pub histogram: HashMap<&'static str, usize>,
+}
+impl AstStats {
+    fn record(&mut self, name: &'static str) {
+        self.node_count += 1;
+        *self.histogram.entry(name).or_insert(0) += 1;
+    }
+
+    fn walk_statement(&mut self, statement: &Statement) {
+        self.record(statement.name());
+        match statement {
+            Statement::ExprStatement(expr)
+            | Statement::Const(_, _, expr)
+            | Statement::Destructure(_, expr) => self.walk_expr(expr, 1),
+            Statement::Print(args) => {
+                for expr in args {
+                    self.walk_expr(expr, 1);
+                }
+            }
+            Statement::Let(declarators) => {
+                for (_, _, expr) in declarators {
+                    let Some(expr) = expr else { continue };
+                    self.walk_expr(expr, 1);
+                }
+            }
+            Statement::Block(statements) => {
+                for statement in statements {
+                    self.walk_statement(statement);
+                }
+            }
+            Statement::DoWhile(body, cond) => {
+                for statement in body {
+                    self.walk_statement(statement);
+                }
+                self.walk_expr(cond, 1);
+            }
+            Statement::For(init, cond, step, body) => {
+                if let Some(init) = init {
+                    self.walk_statement(init);
+                }
+                for expr in [cond, step].into_iter().flatten() {
+                    self.walk_expr(expr, 1);
+                }
+                for statement in body {
+                    self.walk_statement(statement);
+                }
+            }
+        }
+    }
+
+    fn walk_expr(&mut self, expr: &Expr, depth: usize) {
+        self.record(expr.name());
+        self.max_depth = self.max_depth.max(depth);
+        for child in expr.children() {
+            self.walk_expr(child, depth + 1);
+        }
+    }
+}
+
+pub fn ast_stats(stmts: &[Box<Spanned<Statement>>]) -> AstStats {
+    let mut stats = AstStats::default();
+    for statement in stmts {
+        stats.walk_statement(statement);
+    }
+    stats
+}
@@ -0,0 +1,1608 @@
+// Most of these run whole programs through the real parser and interpreter.
+use crate::ast::{Expr, NumType, Statement, strip_parens};
+use crate::{CoercionMode, InterpError, OverflowPolicy, TS2G, Ts2gError, Value, parse};
+
+// What the program printed, one entry per `print`.
+pub(crate) fn run(source: &str) -> Result<Vec<String>, Ts2gError> {
+    TS2G::init().evaluate(source).map(|result| result.printed)
+}
+
+pub(crate) fn run_with(policy: OverflowPolicy, source: &str) -> Result<Vec<String>, Ts2gError> {
+    TS2G::builder()
+        .overflow(policy)
+        .build()
+        .evaluate(source)
+        .map(|result| result.printed)
+}
+
+pub(crate) fn runtime_error(source: &str) -> InterpError {
+    match run(source) {
+        Err(Ts2gError::Runtime(e) | Ts2gError::RuntimeAt { error: e, .. }) => e,
+        other => panic!(
+            "expected a runtime error from {:?}, got {:?}",
+            source, other
+        ),
+    }
+}
+
+#[test]
+fn readme_sample() {
+    assert_eq!(
+        run("let x:u64=1+1;print(x);x=x+10;print(x);").unwrap(),
+        ["2", "12"]
+    );
+}
+
+#[test]
+fn declarations() {
+    assert_eq!(
+        run("let a = 1, b: u8 = 2; const c: i32 = -3; print(a, b, c);").unwrap(),
+        ["1 2 -3"]
+    );
+    assert_eq!(
+        run("let s: string = \"hi\"; let t = true; print(s, t, !t);").unwrap(),
+        ["hi true false"]
+    );
+    assert!(matches!(
+        runtime_error("const c: u8 = 1; c = 2;"),
+        InterpError::AssignToConst(_)
+    ));
+    assert!(matches!(
+        runtime_error("print(nope);"),
+        InterpError::Undefined(_)
+    ));
+}
+
+#[test]
+fn arithmetic_and_casts() {
+    assert_eq!(
+        run("let a: u32 = 7; print(a / 2, a * 3 - 1);").unwrap(),
+        ["3 20"]
+    );
+    assert_eq!(
+        run("let w: u32 = 300; print(w as u8, 300 as u8, saturate(w, u8), 3.7 as u8);").unwrap(),
+        ["44 44 255 3"]
+    );
+    // Prefix operators apply before the cast, and whole literals convert like integers.
+    assert_eq!(
+        run("let x: u8 = 5; print(-128 as i8, -1 as u8, ~0 as u8, ~x as u16, -3.7 as i8);")
+            .unwrap(),
+        ["-128 255 255 250 -3"]
+    );
+    assert_eq!(
+        run("let a: i32 = 64; print(a << 4, a >> 2, ~a);").unwrap(),
+        ["1024 16 -65"]
+    );
+    assert!(matches!(
+        runtime_error("let a: u8 = 255; a = a + 1;"),
+        InterpError::Overflow("+", _)
+    ));
+}
+
+#[test]
+fn control_flow() {
+    assert_eq!(
+        run("let s: u32 = 0; for (let i: u32 = 0; i < 4; i++) { s = s + i; } print(s);").unwrap(),
+        ["6"]
+    );
+    assert_eq!(
+        run("let n = 0; { let n = 5; print(n); } print(n);").unwrap(),
+        ["5", "0"]
+    );
+    assert_eq!(
+        run("print(1 < 2 && 2 < 3 ? \"yes\" : \"no\");").unwrap(),
+        ["yes"]
+    );
+}
+
+#[test]
+fn strings_arrays_templates() {
+    assert_eq!(
+        run("let a = [1, 2, 3]; print(a[1], a.length, typeof a);").unwrap(),
+        ["2 3 array"]
+    );
+    assert_eq!(
+        run("let x = 2; print(`x is ${x + 1}`);").unwrap(),
+        ["x is 3"]
+    );
+    assert!(matches!(
+        runtime_error("let a = [1]; print(a[5]);"),
+        InterpError::IndexOutOfBounds(5, 1)
+    ));
+}
+
+#[test]
+fn comments_and_empty_programs() {
+    assert_eq!(run("").unwrap(), Vec::<String>::new());
+    assert_eq!(
+        run("// only a comment\n/* and a block */").unwrap(),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        run("print(1); // trailing\n/* x */ print(2);").unwrap(),
+        ["1", "2"]
+    );
+}
+
+#[test]
+fn syntax_errors_have_positions() {
+    match run("let x = 1;\nlet = 2;") {
+        Err(Ts2gError::Parse(e)) => assert_eq!((e.line, e.col), (2, 5)),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn call_statements() {
+    assert_eq!(run("let y = sqrt(16); print(y);").unwrap(), ["4"]);
+    assert_eq!(run("let x = 2; abs(x); print(x);").unwrap(), ["2"]);
+    let printed = run("let r = Math.random(); print(r >= 0 && r < 1);").unwrap();
+    assert_eq!(printed, ["true"]);
+    assert_eq!(
+        run("print(max(1, 2));\nprint(min(3, 4))").unwrap(),
+        ["2", "3"]
+    );
+}
+
+#[test]
+fn do_while_runs_at_least_once() {
+    assert_eq!(
+        run("let i = 0; do { print(i); i = i + 1; } while (i < 3);").unwrap(),
+        ["0", "1", "2"]
+    );
+    assert_eq!(
+        run("let c = false; do { print(\"once\"); } while (c);").unwrap(),
+        ["once"]
+    );
+}
+
+#[test]
+fn do_while_without_semicolons() {
+    let source = "let c = false\ndo {\n  print(\"once\")\n} while (c)\nprint(\"after\")\n";
+    assert_eq!(run(source).unwrap(), ["once", "after"]);
+}
+
+#[test]
+fn assert_statements() {
+    assert_eq!(
+        run("let x = true; assert(x); assert(1 < 2, \"math\"); print(\"ok\");").unwrap(),
+        ["ok"]
+    );
+    assert!(matches!(
+        runtime_error("assert(1 > 2);"),
+        InterpError::AssertionFailed(None)
+    ));
+    assert!(matches!(
+        runtime_error("assert(false, \"nope\"); print(1);"),
+        InterpError::AssertionFailed(Some(m)) if m == "nope"
+    ));
+}
+
+#[test]
+fn call_statements_leave_the_stack_balanced() {
+    // Void and value-returning calls alike; the per-statement stack check runs in test builds.
+    assert_eq!(
+        run("let x = 9; sqrt(x); floor(x); Math.random(); assert(true); print(x);").unwrap(),
+        ["9"]
+    );
+    assert_eq!(run("let x = 9\nsqrt(x)\nprint(x)").unwrap(), ["9"]);
+}
+
+#[test]
+fn tuple_destructuring() {
+    assert_eq!(run("let [x, y] = (1, 2); print(x, y);").unwrap(), ["1 2"]);
+    assert_eq!(
+        run("let a = 1, b = 2; let x = 0; x = (a + b); print(x);").unwrap(),
+        ["3"]
+    );
+    assert_eq!(
+        run("let [s, t] = (\"a\", true,); print(s, t);").unwrap(),
+        ["a true"]
+    );
+    assert_eq!(run("let [one] = (7,); print(one);").unwrap(), ["7"]);
+    assert!(matches!(
+        runtime_error("print((1, 2) + 3);"),
+        InterpError::MisplacedTuple
+    ));
+    assert!(matches!(
+        runtime_error("let t = (1, 2);"),
+        InterpError::MisplacedTuple
+    ));
+}
+
+#[test]
+fn saturate_takes_any_expression() {
+    assert_eq!(
+        run("let a: u32 = 200; print(saturate(a * 2, u8), saturate(a - 100 as u32, u8));").unwrap(),
+        ["255 100"]
+    );
+}
+
+fn printed(source: &str) -> String {
+    let statements =
+        parse(source).unwrap_or_else(|e| panic!("{:?} doesn't parse: {:?}", source, e));
+    statements
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn expr(source: &str) -> Expr {
+    match parse(source).unwrap().remove(0).node {
+        Statement::ExprStatement(expr) => *expr,
+        other => panic!("not an expression statement: {}", other),
+    }
+}
+
+#[test]
+fn exponent_precedence() {
+    let grouped = |source: &str, explicit: &str| {
+        let mut implicit = Box::new(expr(source));
+        let mut explicit = Box::new(expr(explicit));
+        strip_parens(&mut implicit);
+        strip_parens(&mut explicit);
+        assert_eq!(
+            implicit, explicit,
+            "{:?} should group as {:?}",
+            source, explicit
+        );
+    };
+    grouped("2 * 3 ** 2;", "2 * (3 ** 2);");
+    grouped("2 ** 3 * 2;", "(2 ** 3) * 2;");
+    grouped("a / b ** c;", "a / (b ** c);");
+    grouped("2 ** 3 ** 2;", "2 ** (3 ** 2);");
+    grouped("-a ** 2;", "-(a ** 2);");
+    grouped("a ** -b;", "a ** (-b);");
+    grouped("a ** -b ** c;", "a ** (-(b ** c));");
+    grouped("a ** b as u8;", "(a ** b) as u8;");
+    // Unary minus sits between `**` and `as`.
+    grouped("-a as u8;", "(-a) as u8;");
+    grouped("~a as u16;", "(~a) as u16;");
+    grouped("-a ** b as u8;", "(-(a ** b)) as u8;");
+    grouped("!a as u8 as u16;", "((!a) as u8) as u16;");
+    assert_eq!(
+        run("print(2 * 3 ** 2, 2 ** 3 ** 2, (2 * 3) ** 2, -2 ** 2, (-2) ** 2, 2 ** -1, 12 / 2 ** 2);")
+            .unwrap(),
+        ["18 512 36 -4 4 0.5 3"]
+    );
+    assert_eq!(
+        run("let b: u8 = 3; let n: i32 = 2; print(b * b ** b, -n * n ** n * n);").unwrap(),
+        ["81 -16"]
+    );
+}
+
+#[test]
+fn display_round_trips_expressions() {
+    for source in [
+        "a - (b - c);",
+        "(a - b) - c;",
+        "a ** b ** c;",
+        "(a ** b) ** c;",
+        "-(a + b) * c;",
+        "a < b == (c < d);",
+        "a ? b : c ? d : e;",
+        "(a ? b : c) ? d : e;",
+        "x = y = 1 + 2;",
+        "(x as u8) << 2;",
+        "-a as u8;",
+        "-(a as u8) * -b ** 2;",
+        "saturate(a * 2, u16) & ~b;",
+        "!(a && b) || c;",
+        "typeof [1, 2.5, 3][i];",
+        "Math.max(a, b) + sqrt(c);",
+        "`a ${b + 1} c`;",
+        "\"q\\\"uote\";",
+        "(1, a + 2, \"s\");",
+        "(a,);",
+    ] {
+        let once = expr(source);
+        let again = expr(&format!("{};", once));
+        assert_eq!(once, again, "{:?} printed as {}", source, once);
+        // Without the parentheses from the source, Display has to put back the ones it needs.
+        let mut bare = Box::new(once);
+        strip_parens(&mut bare);
+        let mut reparsed = Box::new(expr(&format!("{};", bare)));
+        strip_parens(&mut reparsed);
+        assert_eq!(bare, reparsed, "{:?} printed as {}", source, bare);
+    }
+}
+
+#[test]
+fn display_round_trips_statements() {
+    let source = "let a = 1, b: u8, c: i32 = -2; const k: f64 = 0.5; let [x, y] = (1, 2); \
+        print(a, b + 1); { let z = a; } for (let i = 0; i < 3; i++) { print(i); } \
+        for (;;) { } do { a = a + 1; } while (a < 10);";
+    let once = printed(source);
+    assert_eq!(once, printed(&once));
+}
+
+#[test]
+fn shifts_past_the_bit_width_follow_the_overflow_policy() {
+    let source = "let a: u8 = 3; let b: i8 = -8; print(a << 9, a >> 8, b >> 10, b << 8);";
+    assert!(matches!(
+        run_with(OverflowPolicy::Checked, source),
+        Err(Ts2gError::RuntimeAt {
+            error: InterpError::InvalidArgument(_),
+            ..
+        })
+    ));
+    // Wrapping takes the amount modulo the width, like Rust's wrapping_shl.
+    assert_eq!(
+        run_with(OverflowPolicy::Wrapping, source).unwrap(),
+        ["6 3 -2 -8"]
+    );
+    // Saturating shifts every bit out, leaving zeros or the sign.
+    assert_eq!(
+        run_with(OverflowPolicy::Saturating, source).unwrap(),
+        ["0 0 -1 0"]
+    );
+    for policy in [
+        OverflowPolicy::Checked,
+        OverflowPolicy::Wrapping,
+        OverflowPolicy::Saturating,
+    ] {
+        assert_eq!(
+            run_with(policy, "let a: u8 = 3; print(a << 7);").unwrap(),
+            ["128"]
+        );
+        assert!(run_with(policy, "let a: i32 = 3; let n: i32 = -1; print(a << n);").is_err());
+    }
+}
+
+#[test]
+fn reset_between_programs() {
+    let mut ts2g = TS2G::init();
+    let first = "let unused = 1; let x = 2; { let y = x; print(y); print(nope); }";
+    assert!(ts2g.evaluate(first).is_err());
+    assert!(ts2g.error_span().is_some());
+    assert!(!ts2g.warnings().is_empty());
+
+    ts2g.reset();
+    assert_eq!(ts2g.error_span(), None);
+    assert!(ts2g.warnings().is_empty());
+    assert!(matches!(
+        ts2g.evaluate("print(x);"),
+        Err(Ts2gError::RuntimeAt {
+            error: InterpError::Undefined(_),
+            ..
+        })
+    ));
+
+    ts2g.reset();
+    let second = ts2g.evaluate("let x = 3; print(x);").unwrap();
+    assert_eq!(second.printed, ["3"]);
+    assert!(second.warnings.is_empty());
+    assert_eq!(second.vars.len(), 1);
+}
+
+#[test]
+fn builtin_arguments_adopt_literal_types() {
+    assert_eq!(
+        run("let x: u64 = 7; let y: i8 = -3; print(max(x, 0), min(y, 0, 5), clamp(x, 0, 5));")
+            .unwrap(),
+        ["7 -3 5"]
+    );
+    assert_eq!(
+        run("let x: u8 = 9; print(clamp(x, 0, 10) as f64 / 2);").unwrap(),
+        ["4.5"]
+    );
+    // Literals alone stay f64, and one that doesn't fit the other arguments' type still fails.
+    assert_eq!(run("print(max(1, 2.5));").unwrap(), ["2.5"]);
+    assert!(matches!(
+        runtime_error("let x: u8 = 1; print(max(x, 300));"),
+        InterpError::InvalidArgument(_)
+    ));
+}
+
+#[test]
+fn includes_types_a_literal_needle_like_the_elements() {
+    assert_eq!(
+        run("let a = [1 as u8, 2, 3]; print(includes(a, 2), includes(a, 4));").unwrap(),
+        ["true false"]
+    );
+    assert_eq!(
+        run("let a = [1.5, 2]; print(includes(a, 2));").unwrap(),
+        ["true"]
+    );
+    assert_eq!(
+        run("let a = [-1 as i64]; print(includes(a, -1));").unwrap(),
+        ["true"]
+    );
+    // 300 isn't a u8, so it stays f64 and doesn't match the elements' type.
+    assert!(matches!(
+        runtime_error("let a = [1 as u8]; print(includes(a, 300));"),
+        InterpError::TypeMismatch(_, _)
+    ));
+}
+
+#[test]
+fn value_operators_return_errors_instead_of_panicking() {
+    assert_eq!(
+        (Value::from(2u8) + Value::from(3u8)).unwrap(),
+        Value::from(5u8)
+    );
+    assert_eq!(
+        (Value::from(1.5f64) * Value::from(2f64)).unwrap(),
+        Value::from(3f64)
+    );
+    assert!(matches!(
+        Value::from(255u8) + Value::from(1u8),
+        Err(InterpError::Overflow("+", NumType::U8))
+    ));
+    assert!(matches!(
+        Value::from(0u32) - Value::from(1u32),
+        Err(InterpError::Overflow("-", NumType::U32))
+    ));
+    assert!(matches!(
+        Value::from(i64::MIN) / Value::from(-1i64),
+        Err(InterpError::Overflow("/", NumType::I64))
+    ));
+    assert!(matches!(
+        Value::from(7i32) / Value::from(0i32),
+        Err(InterpError::DivideByZero)
+    ));
+    assert!((Value::from(1u64) + Value::from(1i64)).is_err());
+}
+
+fn js(source: &str) -> String {
+    crate::codegen::to_js(&parse(source).unwrap())
+}
+
+#[test]
+fn js_for_the_sample_program() {
+    assert_eq!(
+        js("let x:u64=1+1;print(x);x=x+10;print(x);"),
+        "let x = 1 + 1;\nconsole.log(x);\nx = x + 10;\nconsole.log(x);\n"
+    );
+}
+
+#[test]
+fn js_truncates_integer_division_and_wraps_small_integers() {
+    assert_eq!(
+        js("let a: u8 = 250; a = a + 10; let b: i32 = -7; print(b / 2, 7 / 2);"),
+        "let a = 250;\na = a + 10 & 255;\nlet b = -7;\nconsole.log(Math.trunc(b / 2) | 0, 7 / 2);\n"
+    );
+    assert_eq!(
+        js("let c: u32 = 0; c = c - 1; print(c >> 1, (c & 3) * 2);"),
+        "let c = 0;\nc = c - 1 >>> 0;\nconsole.log(c >>> 1, ((c & 3) >>> 0) * 2 >>> 0);\n"
+    );
+    // A discarded increment is written like a prefix one; a used one steps back afterwards.
+    assert_eq!(
+        js("let d: i8 = 127; d++; let g = d++; let w: u32 = 300; print(w as u8, ~d);"),
+        "let d = 127;\nd = d + 1 << 24 >> 24;\nlet g = (d = d + 1 << 24 >> 24) - 1 << 24 >> 24;\n\
+         let w = 300;\nconsole.log(w & 255, ~d << 24 >> 24);\n"
+    );
+    // Whole literals wrap into the cast type, as the interpreter converts them.
+    assert_eq!(
+        js("print(-1 as u8, ~0 as u8, -128 as i8, 300 as u8, 2.5 as u8);"),
+        "console.log(-1 & 255, ~0 & 255, -128 << 24 >> 24, 300 & 255, Math.trunc(2.5));\n"
+    );
+    // 64-bit integers don't fit in a double, so only their division is adjusted.
+    assert_eq!(
+        js("let n: u64 = 7; for (let i: u16 = 0; i < n; i++) { print(n / 2, i + 1); }"),
+        "let n = 7;\nfor (let i = 0; i < n; i = i + 1 & 65535) {\n    console.log(Math.trunc(n / 2), i + 1 & 65535);\n}\n"
+    );
+}
+
+fn glsl(source: &str) -> Result<String, crate::codegen::CodegenError> {
+    crate::codegen::to_glsl(&parse(source).unwrap())
+}
+
+#[test]
+fn glsl_for_a_small_program() {
+    assert_eq!(
+        glsl(
+            "let x: u32 = 1 + 2; let y: f32 = 0.5; const k: i32 = -4; \
+             for (let i: u32 = 0; i < x; i++) { x = x * 2; y = y * 2; } \
+             let z = max(x, 7) >> 1;"
+        )
+        .unwrap(),
+        "#version 450\n\nvoid main() {\n    uint x = 1u + 2u;\n    float y = 0.5;\n    \
+         const int k = -4;\n    for (uint i = 0u; i < x; i++) {\n        x = x * 2u;\n        \
+         y = y * 2.0;\n    }\n    uint z = max(x, 7u) >> 1u;\n}\n"
+    );
+}
+
+#[test]
+fn glsl_rejects_literals_out_of_range() {
+    assert!(glsl("let x: u32 = 4294967295;").is_ok());
+    for source in [
+        "let x: u32 = 4294967296;",
+        "let x: u32 = 1; x = x + 5000000000;",
+        "let x: i32 = 2147483648;",
+        "let x: u32 = 1.5;",
+        "let x: u64 = 1;",
+    ] {
+        assert!(glsl(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn warnings_point_at_the_declaring_statement() {
+    let source = "let a = 1\nprint(a);\n{\n    let b = 2\n}\nfor (let i = 0; a < 0;) { }\n";
+    let warnings = TS2G::init().evaluate(source).unwrap().warnings;
+    let found: Vec<_> = warnings
+        .iter()
+        .map(|w| (&source[w.span.start..w.span.end], w.msg.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("let b = 2", "'b' is declared but never read"),
+            ("let i = 0;", "'i' is declared but never read"),
+        ]
+    );
+}
+
+#[test]
+fn large_integer_literals_are_exact() {
+    // Both round to the same f64, so only a native comparison tells them apart.
+    assert_eq!(
+        run(
+            "let a: u64 = 18446744073709551615; let b: u64 = 18446744073709551614; \
+             print(max(a, b), min(b, a), a == b, a - b);"
+        )
+        .unwrap(),
+        ["18446744073709551615 18446744073709551614 false 1"]
+    );
+    assert_eq!(
+        run("let a: u64 = 9007199254740993; print(max(a, 9007199254740992));").unwrap(),
+        ["9007199254740993"]
+    );
+    assert_eq!(
+        run("let m: i64 = -9223372036854775808; let u: u128 = \
+             340282366920938463463374607431768211455; print(m, u);")
+        .unwrap(),
+        ["-9223372036854775808 340282366920938463463374607431768211455"]
+    );
+    assert!(matches!(
+        runtime_error("let a: u64 = 18446744073709551616;"),
+        InterpError::LiteralOutOfRange(n, NumType::U64) if n == "18446744073709551616"
+    ));
+    assert!(matches!(
+        runtime_error("let a: i64 = -9223372036854775809;"),
+        InterpError::LiteralOutOfRange(_, NumType::I64)
+    ));
+    assert!(matches!(
+        run("let a = 340282366920938463463374607431768211456;"),
+        Err(Ts2gError::Parse(_))
+    ));
+    // Without an annotation a literal is still an f64.
+    assert_eq!(
+        run("let f = 3; print(typeof f, f / 2, 2.0);").unwrap(),
+        ["f64 1.5 2"]
+    );
+}
+
+#[test]
+fn hex_and_binary_literals() {
+    assert_eq!(
+        run("let a: u8 = 0xff; let b: u16 = 0b1010; let c: u64 = 0xFFFFFFFFFFFFFFFF; print(a, b, c, 0x10);")
+            .unwrap(),
+        ["255 10 18446744073709551615 16"]
+    );
+    assert_eq!(run("let a: u8 = 0x0f\nprint(a & 0b11)").unwrap(), ["3"]);
+    assert!(matches!(
+        runtime_error("let a: u8 = 0x100;"),
+        InterpError::LiteralOutOfRange(n, NumType::U8) if n == "256"
+    ));
+    assert!(matches!(
+        runtime_error("let a: u32 = -0b1;"),
+        InterpError::LiteralOutOfRange(_, NumType::U32)
+    ));
+    assert!(matches!(
+        run("let a = 0x100000000000000000000000000000000;"),
+        Err(Ts2gError::Parse(_))
+    ));
+}
+
+// Tokens for the fuzzer: language pieces, a few words it lacks, and unterminated strings,
+// templates and comments. Strung together at random they make mostly invalid programs.
+const FUZZ_TOKENS: &str = "let const if else while do for break continue print assert saturate \
+    typeof as true false e pi Math.PI x y arr s u8 i8 u16 i32 u32 u64 i64 u128 i128 f32 f64 \
+    usize isize bool string 0 1 -1 255 256 3.5 1e308 0xff 0b101 0x 18446744073709551616 \
+    340282366920938463463374607431768211456 + - * / % ** << >> >>> & | ^ ~ ! && || == != < >= \
+    = += <<= **= ++ -- ? : ( ) [ ] { } , ; . \"ab\" \"open `t${x}` `open${ /* // max min abs \
+    floor sqrt random includes length push";
+
+// A random well-formed expression over `x` (u8), `y` (i32), `z` (u64) and `f` (f64), so the
+// interpreter sees overflow, division by zero, bad shifts and mixed types rather than parse errors.
+fn fuzz_expr(rng: &mut crate::builtins::Rng, depth: u32) -> String {
+    const LEAVES: &[&str] = &[
+        "x", "y", "z", "f", "0", "1", "-1", "7", "255", "3.5", "0xff", "pi",
+    ];
+    const OPS: &[&str] = &[
+        "+", "-", "*", "/", "**", "<<", ">>", "&", "|", "^", "==", "<", "&&", "||",
+    ];
+    const TYPES: &[&str] = &["u8", "i8", "u16", "i32", "u32", "u64", "i64", "f32", "f64"];
+    let pick = |rng: &mut crate::builtins::Rng, from: &[&'static str]| {
+        from[rng.next_u64() as usize % from.len()]
+    };
+    if depth == 0 {
+        return pick(rng, LEAVES).to_string();
+    }
+    match rng.next_u64() % 6 {
+        0 => format!("-({})", fuzz_expr(rng, depth - 1)),
+        1 => format!("({} as {})", fuzz_expr(rng, depth - 1), pick(rng, TYPES)),
+        2 => format!(
+            "saturate({}, {})",
+            fuzz_expr(rng, depth - 1),
+            pick(rng, TYPES)
+        ),
+        3 => format!(
+            "max({}, {})",
+            fuzz_expr(rng, depth - 1),
+            fuzz_expr(rng, depth - 1)
+        ),
+        _ => format!(
+            "({} {} {})",
+            fuzz_expr(rng, depth - 1),
+            pick(rng, OPS),
+            fuzz_expr(rng, depth - 1)
+        ),
+    }
+}
+
+// A few thousand generated programs, each run under every overflow policy. Any result is
+// fine as long as it comes back as `Ok` or a `Ts2gError` rather than a panic.
+#[test]
+fn evaluate_never_panics() {
+    let samples = [
+        "let x: u8 = 250; x += 10; print(x, x << 9, -x);",
+        "let a = [1, 2, 3]; for (let i = 0; i < 5; i++) { print(a[i] / (i - 1)); }",
+        "let i: i8 = -128; print(i / -1, i % -1, i ** 3, abs(i), saturate(i * 2, u8));",
+        "let s = `v${1 / 0}`; do { s += s; } while (s.length < 100); print(s);",
+        "let (p, q) = (1 as u64, 2); print(p - q as u64 >> 70, max(p, 0xffffffffffffffff));",
+    ];
+    let mut tokens: Vec<&str> = FUZZ_TOKENS.split_whitespace().collect();
+    tokens.push("\n");
+    let mut rng = crate::builtins::Rng::new(856);
+    let mut inputs = Vec::new();
+    for _ in 0..2000 {
+        let len = rng.next_u64() % 24 + 1;
+        let soup: Vec<&str> = (0..len)
+            .map(|_| tokens[rng.next_u64() as usize % tokens.len()])
+            .collect();
+        inputs.push(soup.join(" "));
+    }
+    for _ in 0..1000 {
+        let mut bytes = samples[rng.next_u64() as usize % samples.len()]
+            .as_bytes()
+            .to_vec();
+        for _ in 0..rng.next_u64() % 4 + 1 {
+            let at = rng.next_u64() as usize % bytes.len();
+            match rng.next_u64() % 3 {
+                0 => {
+                    bytes.remove(at);
+                }
+                1 => bytes.insert(at, b"(){};+-*/\"`$0x9"[rng.next_u64() as usize % 15]),
+                _ => {
+                    let other = rng.next_u64() as usize % bytes.len();
+                    bytes.swap(at, other);
+                }
+            }
+        }
+        inputs.push(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    for _ in 0..1000 {
+        let mut program =
+            String::from("let x: u8 = 200; let y: i32 = -5; let z: u64 = 3; let f = 0.5;");
+        for _ in 0..rng.next_u64() % 4 + 1 {
+            let depth = (rng.next_u64() % 4) as u32;
+            let e = fuzz_expr(&mut rng, depth);
+            program += &match rng.next_u64() % 4 {
+                0 => format!(" x = {};", e),
+                1 => format!(" y = y - ({});", e),
+                2 => format!(" z = {} ? z++ : z / 0;", e),
+                _ => format!(" print({});", e),
+            };
+        }
+        inputs.push(program);
+    }
+    let policies = [
+        OverflowPolicy::Checked,
+        OverflowPolicy::Wrapping,
+        OverflowPolicy::Saturating,
+    ];
+    for input in &inputs {
+        for policy in policies {
+            let outcome = std::panic::catch_unwind(|| {
+                TS2G::builder()
+                    .overflow(policy)
+                    .max_steps(10_000)
+                    .max_depth(64)
+                    .build()
+                    .evaluate(input)
+                    .map(|_| ())
+            });
+            assert!(
+                outcome.is_ok(),
+                "evaluate panicked on {:?} ({:?})",
+                input,
+                policy
+            );
+        }
+        // Whatever parses must print back to something that parses again.
+        if let Ok(program) = parse(input) {
+            let text: String = program.iter().map(|s| format!("{}\n", s.node)).collect();
+            assert!(
+                parse(&text).is_ok(),
+                "{:?} printed as unparsable {:?}",
+                input,
+                text
+            );
+        }
+    }
+}
+
+#[test]
+fn negative_literals_reach_the_type_minimum() {
+    assert_eq!(
+        run(
+            "let a: i8 = -128; let b: i64 = -9223372036854775808; let c: i16 = -(32768); \
+             print(a, b, c);"
+        )
+        .unwrap(),
+        ["-128 -9223372036854775808 -32768"]
+    );
+    // The sign belongs to the literal before any cast.
+    assert_eq!(
+        run("let m: i8 = -128 as i8; print(m, -128 as i8 == m, typeof (-1 as i64));").unwrap(),
+        ["-128 true i64"]
+    );
+    assert!(matches!(
+        runtime_error("let y: i8 = 128;"),
+        InterpError::LiteralOutOfRange(n, NumType::I8) if n == "128"
+    ));
+    assert!(matches!(
+        runtime_error("let y: i8 = -129;"),
+        InterpError::LiteralOutOfRange(n, NumType::I8) if n == "-129"
+    ));
+    assert!(matches!(
+        runtime_error("let y: u8 = -1;"),
+        InterpError::LiteralOutOfRange(_, NumType::U8)
+    ));
+    // Only a literal folds its sign; negating a variable that holds the minimum still overflows.
+    assert!(matches!(
+        runtime_error("let a: i8 = -128; print(-a);"),
+        InterpError::Overflow("-", NumType::I8)
+    ));
+}
+
+#[test]
+fn right_shift_is_arithmetic_for_signed_and_logical_for_unsigned() {
+    assert_eq!(
+        run(
+            "let x: i32 = -8; let y: u8 = 0xF0; let z: i8 = -1; let w: u32 = 0x80000000; \
+             print(x >> 1, y >> 4, y >> 4 == 0x0F, z >> 7, w >> 31);"
+        )
+        .unwrap(),
+        ["-4 15 true -1 1"]
+    );
+    // The same bits read as unsigned shift in zeros.
+    assert_eq!(
+        run("let z: i8 = -1; print(z as u8 >> 7, (z >> 4) as u8);").unwrap(),
+        ["1 255"]
+    );
+}
+
+// One overflowing i8 (or u8) program per operator family, paired with the operator's name.
+const OVERFLOWS: [(&str, &str); 7] = [
+    ("-", "let m: i8 = -128; print(-m);"),
+    ("-", "let u: u8 = 1; print(-u);"),
+    ("+", "let m: i8 = 127; let n: i8 = 1; print(m + n);"),
+    ("-", "let m: i8 = -128; let n: i8 = 1; print(m - n);"),
+    ("*", "let m: i8 = 64; let n: i8 = 2; print(m * n);"),
+    ("/", "let m: i8 = -128; let n: i8 = -1; print(m / n);"),
+    ("**", "let m: i8 = 2; let n: i8 = 7; print(m ** n);"),
+];
+
+#[test]
+fn checked_overflow_is_an_error_for_every_operator() {
+    for (op, source) in OVERFLOWS {
+        match run_with(OverflowPolicy::Checked, source) {
+            Err(Ts2gError::RuntimeAt {
+                error: InterpError::Overflow(name, _),
+                ..
+            }) => assert_eq!(name, op, "{:?}", source),
+            other => panic!("{:?} gave {:?}", source, other),
+        }
+    }
+    assert_eq!(
+        run_with(OverflowPolicy::Checked, "let u: u8 = 0; print(-u);").unwrap(),
+        ["0"]
+    );
+}
+
+#[test]
+fn wrapping_overflow_wraps_for_every_operator() {
+    let wrapped = ["-128", "255", "-128", "127", "-128", "-128", "-128"];
+    for ((_, source), expected) in OVERFLOWS.into_iter().zip(wrapped) {
+        assert_eq!(
+            run_with(OverflowPolicy::Wrapping, source).unwrap(),
+            [expected],
+            "{:?}",
+            source
+        );
+    }
+}
+
+#[test]
+fn saturating_overflow_clamps_for_every_operator() {
+    let clamped = ["127", "0", "127", "-128", "127", "127", "127"];
+    for ((_, source), expected) in OVERFLOWS.into_iter().zip(clamped) {
+        assert_eq!(
+            run_with(OverflowPolicy::Saturating, source).unwrap(),
+            [expected],
+            "{:?}",
+            source
+        );
+    }
+}
+
+#[test]
+fn batches_split_at_marker_lines_and_run_each_program_fresh() {
+    let source = "let a = 1;\nprint(a\n---\nlet a: u8 = 2; print(a);\n  ---  \r\nprint(a);";
+    let programs = crate::split_programs(source);
+    assert_eq!(
+        programs,
+        [
+            "let a = 1;\nprint(a\n",
+            "let a: u8 = 2; print(a);\n",
+            "print(a);"
+        ]
+    );
+    assert!(parse(programs[0]).is_err());
+    assert_eq!(run(programs[1]).unwrap(), ["2"]);
+    // The parse error and the third program's undefined `a` are both counted, and neither stops
+    // the program between them.
+    assert!(matches!(
+        crate::run_batch(TS2G::init(), &programs),
+        Err(Ts2gError::Batch {
+            failed: 2,
+            total: 3
+        })
+    ));
+    assert_eq!(crate::split_programs("print(1);\n"), ["print(1);\n"]);
+}
+
+#[test]
+fn time_budget_stops_an_endless_loop() {
+    let budget = std::time::Duration::from_millis(50);
+    let mut ts2g = TS2G::builder().time_budget(budget).build();
+    let start = std::time::Instant::now();
+    let result = ts2g.evaluate("let i = 0; for (;;) { i = i + 1; }");
+    assert!(matches!(
+        result,
+        Err(Ts2gError::RuntimeAt {
+            error: InterpError::TimeLimitExceeded(b),
+            ..
+        }) if b == budget
+    ));
+    // Checked every few statements, so it overshoots by far less than a second even in debug.
+    assert!(start.elapsed() < budget + std::time::Duration::from_secs(1));
+    // The budget is per evaluation, not spent once for the interpreter's lifetime.
+    assert_eq!(ts2g.evaluate("print(1);").unwrap().printed, ["1"]);
+}
+
+#[test]
+fn statements_leave_the_stack_empty() {
+    let mut ts2g = TS2G::init();
+    let program = parse("let x:u64=1+1;print(x);x=x+10;print(x);").unwrap();
+    // Run one statement at a time so a leftover value is caught right after the one that left it.
+    for statement in program {
+        ts2g.run(vec![statement]).unwrap();
+        assert!(ts2g.stack.is_empty(), "left on the stack: {:?}", ts2g.stack);
+    }
+    // Assignments, chained assignments, increments and nested bodies, still checked per statement
+    // by the debug assertion in `visit_statement`.
+    let result = ts2g.evaluate(
+        "let a = 1, b = 2; a = b = 3; a++; --b; \
+         for (let i = 0; i < 2; i++) { a = a + i; { b = b * 2; } } \
+         do { a--; } while (a > 4); print(a, b);",
+    );
+    assert_eq!(result.unwrap().printed, ["4 8"]);
+    assert!(ts2g.stack.is_empty());
+}
+
+#[test]
+fn template_literals_interpolate_values() {
+    assert_eq!(run("print(`sum=${1+2}`);").unwrap(), ["sum=3"]);
+    assert_eq!(
+        run("let x: u8 = 7; let f = 2.5; \
+             print(`x is ${x}`, `${x}${f}`, `sum ${x + 1} and ${f * 2}!`, `${[1, 2][1]}`);")
+        .unwrap(),
+        ["x is 7 72.5 sum 8 and 5! 2"]
+    );
+    // Escapes, a brace inside an embedded string, and text that only looks like interpolation.
+    assert_eq!(
+        run("let x = 1; print(`a \\` b`, `\\${x}`, `${\"}\"}`, `$x {x}`, ``);").unwrap(),
+        ["a ` b ${x} } $x {x} "]
+    );
+    for source in ["print(`${x`);", "print(`${}`);", "print(`a);"] {
+        assert!(
+            matches!(run(source), Err(Ts2gError::Parse(_))),
+            "{:?}",
+            source
+        );
+    }
+}
+
+#[test]
+fn dot_shows_an_add_node_with_its_two_numbers() {
+    let dot = crate::dot::to_dot(&parse("1+2;").unwrap());
+    assert!(dot.starts_with("digraph AST {\n") && dot.ends_with("}\n"));
+    let mut labels = std::collections::HashMap::new();
+    let mut edges = Vec::new();
+    for line in dot.lines().map(str::trim) {
+        if let Some((id, label)) = line.split_once(" [label=\"") {
+            labels.insert(id, label.trim_end_matches("\"];"));
+        } else if let Some((from, to)) = line.split_once(" -> ") {
+            edges.push((from, to.trim_end_matches(';')));
+        }
+    }
+    let add = labels.iter().find(|(_, label)| **label == "Add").unwrap().0;
+    let children: Vec<_> = edges
+        .iter()
+        .filter(|(from, _)| from == add)
+        .map(|(_, to)| labels[to])
+        .collect();
+    assert_eq!(children, ["Number(1)", "Number(2)"]);
+    // The statement wrapping the expression is its parent.
+    let parents: Vec<_> = edges
+        .iter()
+        .filter(|(_, to)| to == add)
+        .map(|(from, _)| labels[from])
+        .collect();
+    assert_eq!(parents, ["ExprStatement"]);
+}
+
+#[test]
+fn ast_stats_count_nodes_depth_and_variants() {
+    let stats = crate::stats::ast_stats(&parse("1+2*3;").unwrap());
+    // ExprStatement, Add, Multiply and three Numbers; the multiply's operands sit at depth 3.
+    assert_eq!(stats.node_count, 6);
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!(
+        stats.histogram,
+        [
+            ("ExprStatement", 1),
+            ("Add", 1),
+            ("Multiply", 1),
+            ("Number", 3)
+        ]
+        .into()
+    );
+
+    let stats = crate::stats::ast_stats(
+        &parse("let a = 1; for (let i = 0; i < 2; i++) { print(-(a + i)); }").unwrap(),
+    );
+    assert_eq!(stats.histogram["Let"], 2);
+    assert_eq!(stats.histogram["Id"], 3);
+    // Neg, Parenthesis, Add, Id.
+    assert_eq!(stats.max_depth, 4);
+    assert_eq!(stats.node_count, stats.histogram.values().sum::<usize>());
+    assert_eq!(crate::stats::ast_stats(&[]), Default::default());
+}
+
+#[test]
+fn ternary_evaluates_only_the_chosen_branch() {
+    assert_eq!(
+        run("let a: u64 = 3, b: u64 = 5; let m: u64 = (a > b) ? a : b; print(m);").unwrap(),
+        ["5"]
+    );
+    // The untaken branch's increment never runs.
+    assert_eq!(
+        run("let i = 0; let t = true ? 1 : i++; let f = false ? i++ : 2; print(i, t, f);").unwrap(),
+        ["0 1 2"]
+    );
+    // Right-associative: the else branch holds the second conditional.
+    assert_eq!(
+        run("print(true ? 1 : false ? 2 : 3, false ? 1 : false ? 2 : 3);").unwrap(),
+        ["1 3"]
+    );
+    assert!(matches!(
+        runtime_error("let a: u8 = 1; let b: u16 = 2; print(true ? a : b);"),
+        InterpError::TypeMismatch(..)
+    ));
+    assert!(matches!(
+        runtime_error("print(1 ? 2 : 3);"),
+        InterpError::ExpectedType(t, _) if t == "bool"
+    ));
+}
+
+#[test]
+fn prefix_and_postfix_increments() {
+    assert_eq!(
+        run("let i: u8 = 5; print(i++, i, ++i, i, i--, i, --i, i);").unwrap(),
+        ["5 6 7 7 7 6 5 5"]
+    );
+    assert!(matches!(
+        runtime_error("print(x++);"),
+        InterpError::Undefined(_)
+    ));
+    assert!(matches!(
+        runtime_error("let s = \"a\"; s++;"),
+        InterpError::ExpectedType(_, t) if t == "string"
+    ));
+    assert!(matches!(
+        runtime_error("let b = true; --b;"),
+        InterpError::ExpectedType(_, t) if t == "bool"
+    ));
+    assert!(matches!(
+        runtime_error("const c: u8 = 1; c++;"),
+        InterpError::AssignToConst(_)
+    ));
+    assert!(matches!(
+        runtime_error("let u: u8 = 0; print(--u);"),
+        InterpError::Overflow("-", NumType::U8)
+    ));
+}
+
+#[test]
+fn string_literals_escapes_and_concatenation() {
+    assert_eq!(
+        run("let s: string = \"a\\tb\\n\\\"q\\\"\\\\\"; print(s + \"!\");").unwrap(),
+        ["a\tb\n\"q\"\\!"]
+    );
+    assert!(matches!(
+        runtime_error("print(\"a\" + 1);"),
+        InterpError::TypeMismatch(..)
+    ));
+    for source in ["print(\"open);", "let s = \"bad \\q\";"] {
+        assert!(
+            matches!(run(source), Err(Ts2gError::Parse(_))),
+            "{:?}",
+            source
+        );
+    }
+    // Editor tooling sees the unterminated string as one error token running to the end.
+    let tokens = crate::lexer::tokenize("print(\"open);");
+    assert_eq!(
+        tokens.last(),
+        Some(&(crate::lexer::TokenKind::Error, 6..13))
+    );
+}
+
+#[test]
+fn step_limit_stops_an_endless_loop() {
+    let mut ts2g = TS2G::builder().max_steps(100).build();
+    assert!(matches!(
+        ts2g.evaluate("let i = 0; for (;;) { i++; }"),
+        Err(Ts2gError::RuntimeAt {
+            error: InterpError::StepLimitExceeded(100),
+            ..
+        })
+    ));
+    // The count starts over for each evaluation, and a short program fits the budget.
+    assert_eq!(ts2g.evaluate("print(1 + 2);").unwrap().printed, ["3"]);
+    // Unlimited unless asked for.
+    assert_eq!(
+        run("let i = 0; for (; i < 10000; i++) { } print(i);").unwrap(),
+        ["10000"]
+    );
+}
+
+#[test]
+fn declarators_bind_left_to_right() {
+    assert_eq!(
+        run("let a = 1, b = a + 1, c: u8 = (b * 2) as u8; print(a, b, c);").unwrap(),
+        ["1 2 4"]
+    );
+    assert_eq!(
+        run("let a: u64 = 1, b: u64 = 2; print(a + b);").unwrap(),
+        ["3"]
+    );
+    // A declarator can't see the ones after it.
+    assert!(matches!(
+        runtime_error("let a = b, b = 1;"),
+        InterpError::Undefined(n) if n == "b"
+    ));
+}
+
+#[test]
+fn print_separates_arguments_with_spaces() {
+    assert_eq!(
+        run("let x: u8 = 3; print(x, \"two\", true, 2.5, x + 1);").unwrap(),
+        ["3 two true 2.5 4"]
+    );
+    assert_eq!(run("print(); print(\"\", \"\");").unwrap(), ["", " "]);
+    // Arguments are evaluated in order.
+    assert_eq!(run("let i = 0; print(i++, i++, i);").unwrap(), ["0 1 2"]);
+}
+
+#[test]
+fn integer_exponents_are_right_associative_and_exact() {
+    assert_eq!(
+        run("let a: i64 = 2, b: i64 = 3, c: i64 = 2; print(a ** b ** c, (a ** b) ** c, -a ** c, (-a) ** b);")
+            .unwrap(),
+        ["512 64 -4 -8"]
+    );
+    // Exact where an f64 round trip would lose the low bits.
+    assert_eq!(
+        run("let t: u64 = 3; let n: u64 = 40; print(t ** n);").unwrap(),
+        ["12157665459056928801"]
+    );
+    assert!(matches!(
+        runtime_error("let a: i32 = 2; let n: i32 = -1; print(a ** n);"),
+        InterpError::InvalidArgument(_)
+    ));
+    // The minus in `x-2` is the operator, not part of the literal.
+    assert_eq!(run("let x = 5; print(x-2);").unwrap(), ["3"]);
+}
+
+#[test]
+fn chained_comparisons_are_a_parse_error() {
+    for (source, col) in [
+        ("print(1 < 2 < 3);", 13),
+        ("let a = 1; print(a >= 0 > 1);", 25),
+    ] {
+        match run(source) {
+            Err(Ts2gError::Parse(e)) => {
+                assert_eq!(e.msg, "chained comparison is not allowed; use &&");
+                assert_eq!((e.line, e.col), (1, col), "{:?}", source);
+            }
+            other => panic!("{:?} gave {:?}", source, other),
+        }
+    }
+    // Equality of two comparisons, or comparisons joined with &&, are fine.
+    assert_eq!(
+        run("print(1 < 2 == true, (1 < 2) == (2 < 3), 1 < 2 && 2 < 3);").unwrap(),
+        ["true true true"]
+    );
+}
+
+#[test]
+fn shebang_lines_are_skipped() {
+    let plain = "let x: u8 = 1;\nprint(x);\n";
+    let script = format!("#!/usr/bin/env ts2g\n{}", plain);
+    let stripped = crate::strip_shebang(&script);
+    let nodes = |source: &str| -> Vec<Statement> {
+        parse(source).unwrap().into_iter().map(|s| s.node).collect()
+    };
+    assert_eq!(nodes(stripped), nodes(plain));
+    assert_eq!(run(stripped).unwrap(), ["1"]);
+    // The newline stays, so errors still report the script's own line numbers.
+    match run(crate::strip_shebang("#!ts2g\nlet = 1;")) {
+        Err(Ts2gError::Parse(e)) => assert_eq!(e.line, 2),
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+    // Only the very first line counts.
+    let later = "print(1);\n#!/usr/bin/env ts2g\n";
+    assert_eq!(crate::strip_shebang(later), later);
+    assert_eq!(crate::strip_shebang(" #!x\n"), " #!x\n");
+}
+
+#[test]
+fn array_destructuring_needs_matching_counts() {
+    assert_eq!(
+        run("let [a, b] = [1, 2]; let xs = [3, 4, 5]; let [p, q, r] = xs; print(a, b, p + q + r);")
+            .unwrap(),
+        ["1 2 12"]
+    );
+    assert!(matches!(
+        runtime_error("let [a, b] = [1, 2, 3];"),
+        InterpError::DestructureMismatch(2, 3)
+    ));
+    assert!(matches!(
+        runtime_error("let [a, b, c] = [1, 2];"),
+        InterpError::DestructureMismatch(3, 2)
+    ));
+    assert!(matches!(
+        runtime_error("let [a] = 1;"),
+        InterpError::ExpectedType(t, _) if t == "array"
+    ));
+    match run("let [a, ...rest] = [1, 2];") {
+        Err(Ts2gError::Parse(e)) => {
+            assert_eq!(e.msg, "rest elements are not supported; name each element")
+        }
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn coercion_mode_decides_whether_mixed_types_widen() {
+    let run_in = |mode, source| {
+        TS2G::builder()
+            .coercion(mode)
+            .build()
+            .evaluate(source)
+            .map(|result| result.printed)
+    };
+    let source = "let a: u8 = 200; let b: u16 = 1000; let f = 0.5; \
+                  print(a + b, typeof (b - a), a < b, b + f, typeof (b / f));";
+    assert!(matches!(
+        run_in(CoercionMode::Strict, source),
+        Err(Ts2gError::RuntimeAt {
+            error: InterpError::TypeMismatch(..),
+            ..
+        })
+    ));
+    assert_eq!(
+        run_in(CoercionMode::Loose, source).unwrap(),
+        ["1200 u16 true 1000.5 f64"]
+    );
+    // Strict is the default.
+    assert!(matches!(
+        runtime_error(source),
+        InterpError::TypeMismatch(..)
+    ));
+    // Even loose mode won't pick a type that can't hold both sides.
+    for source in [
+        "let a: i8 = -1; let b: u8 = 1; print(a + b);",
+        "let a: i64 = -1; let b: u64 = 1; print(a < b);",
+    ] {
+        assert!(matches!(
+            run_in(CoercionMode::Loose, source),
+            Err(Ts2gError::RuntimeAt {
+                error: InterpError::TypeMismatch(..),
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn blank_programs_succeed_with_no_output() {
+    for source in [
+        "",
+        "   \n\t\r\n",
+        "// only a comment",
+        "/* block */\n// line\n",
+    ] {
+        let result = TS2G::init()
+            .evaluate(source)
+            .unwrap_or_else(|e| panic!("{:?} failed: {:?}", source, e));
+        assert!(
+            result.printed.is_empty() && result.vars.is_empty(),
+            "{:?}",
+            source
+        );
+    }
+}
+
+#[test]
+fn newlines_end_statements_without_semicolons() {
+    let nodes = |source: &str| -> Vec<Statement> {
+        parse(source).unwrap().into_iter().map(|s| s.node).collect()
+    };
+    assert_eq!(
+        nodes("let x:u64=1\nprint(x)"),
+        nodes("let x:u64=1;print(x);")
+    );
+    assert_eq!(run("let x:u64=1\nprint(x)").unwrap(), ["1"]);
+    // A line that can't end a statement, or one the next line continues, isn't split.
+    assert_eq!(
+        run("let a = 1 +\n2\nlet b = a\n* 2\nlet c = [1]\n[0]\nprint(a, b, c)").unwrap(),
+        ["3 6 1"]
+    );
+    assert_eq!(nodes("let a = 1\n+ 2"), nodes("let a = 1 + 2;"));
+}
+
+#[test]
+fn value_method_families_at_the_u8_and_i8_boundaries() {
+    type Op = fn(Value, Value) -> Result<Value, InterpError>;
+    // An operator's checked, wrapping and saturating forms, an overflowing pair of u8s, and what
+    // wrapping and saturating make of it.
+    type Family = (&'static str, [Op; 3], (u8, u8), [u8; 2]);
+    let families: [Family; 3] = [
+        (
+            "+",
+            [
+                Value::checked_add,
+                Value::wrapping_add,
+                Value::saturating_add,
+            ],
+            (250, 10),
+            [4, 255],
+        ),
+        (
+            "-",
+            [
+                Value::checked_sub,
+                Value::wrapping_sub,
+                Value::saturating_sub,
+            ],
+            (3, 5),
+            [254, 0],
+        ),
+        (
+            "*",
+            [
+                Value::checked_mul,
+                Value::wrapping_mul,
+                Value::saturating_mul,
+            ],
+            (16, 16),
+            [0, 255],
+        ),
+    ];
+    for (name, [checked, wrapping, saturating], (a, b), [wrapped, clamped]) in families {
+        let (a, b) = (Value::from(a), Value::from(b));
+        assert!(matches!(
+            checked(a, b),
+            Err(InterpError::Overflow(op, NumType::U8)) if op == name
+        ));
+        assert_eq!(wrapping(a, b).unwrap(), Value::from(wrapped), "{}", name);
+        assert_eq!(saturating(a, b).unwrap(), Value::from(clamped), "{}", name);
+    }
+
+    let (min, max, one, neg) = (
+        Value::from(i8::MIN),
+        Value::from(i8::MAX),
+        Value::from(1i8),
+        Value::from(-1i8),
+    );
+    assert!(min.checked_sub(one).is_err() && max.checked_add(one).is_err());
+    assert!(min.checked_mul(neg).is_err());
+    assert_eq!(max.wrapping_add(one).unwrap(), min);
+    assert_eq!(min.wrapping_sub(one).unwrap(), max);
+    assert_eq!(min.wrapping_mul(neg).unwrap(), min);
+    assert_eq!(max.saturating_add(one).unwrap(), max);
+    assert_eq!(min.saturating_sub(one).unwrap(), min);
+    assert_eq!(min.saturating_mul(neg).unwrap(), max);
+    // In range, all three agree.
+    assert_eq!(max.checked_sub(one).unwrap(), Value::from(126i8));
+    assert_eq!(max.wrapping_sub(one).unwrap(), Value::from(126i8));
+    assert_eq!(max.saturating_sub(one).unwrap(), Value::from(126i8));
+    // Mixed types are a mismatch whatever the family.
+    assert!(matches!(
+        Value::from(1u8).wrapping_add(one),
+        Err(InterpError::TypeMismatch(..))
+    ));
+}
+
+#[test]
+fn special_floats_print_like_javascript() {
+    assert_eq!(
+        run("print(0.0 / 0.0, 1.0 / 0.0, -1.0 / 0.0, 1.0 / -0.0);").unwrap(),
+        ["NaN Infinity -Infinity -Infinity"]
+    );
+    assert_eq!(
+        run("let a: f32 = 1; let z: f32 = 0; print(a / z, -a / z, z / z);").unwrap(),
+        ["Infinity -Infinity NaN"]
+    );
+    // Ordinary values keep their usual form, and the names survive interpolation.
+    assert_eq!(
+        run("print(1.0 / 4.0, -0.5, `${1.0 / 0.0}`);").unwrap(),
+        ["0.25 -0.5 Infinity"]
+    );
+}
+
+#[test]
+fn assignments_keep_the_declared_type() {
+    assert_eq!(
+        run(
+            "let x: u8 = 1; x = 200; let y: i64 = 0; let n: u16 = 300; y = n; \
+             let a: u8 = 1, b: u8 = 2; a = b = 7; print(x, typeof x, y, typeof y, a, b);"
+        )
+        .unwrap(),
+        ["200 u8 300 i64 7 7"]
+    );
+    for source in [
+        "let x: u8 = 1; x = 300;",
+        "let a: u8 = 1, b: u8 = 2; a = b = 300;",
+        "let x: u8 = 1; print(x = 300);",
+    ] {
+        assert!(
+            matches!(
+                runtime_error(source),
+                InterpError::LiteralOutOfRange(n, NumType::U8) if n == "300"
+            ),
+            "{:?}",
+            source
+        );
+    }
+    assert!(matches!(
+        runtime_error("let x: u8 = 1; x = \"s\";"),
+        InterpError::InvalidArgument(_)
+    ));
+    // Without an annotation a variable takes whatever it is given.
+    assert_eq!(run("let u = 1; u = \"s\"; print(u);").unwrap(), ["s"]);
+}
+
+#[test]
+fn first_and_later_assignments_fail_alike() {
+    let first = runtime_error("let x: u8; x = 300;");
+    let later = runtime_error("let x: u8 = 1; x = 300;");
+    assert_eq!(first.to_string(), later.to_string());
+    assert!(matches!(
+        later,
+        InterpError::LiteralOutOfRange(_, NumType::U8)
+    ));
+}
+
+#[test]
+fn zero_and_one_for_every_type() {
+    for t in NumType::ALL {
+        let (zero, one) = (Value::zero(t), Value::one(t));
+        assert_eq!((zero.t, one.t), (t, t));
+        assert_eq!(zero.to_string(), "0", "{}", t);
+        assert_eq!(one.to_string(), "1", "{}", t);
+        assert_eq!((one + one).unwrap().to_string(), "2", "{}", t);
+        assert_eq!((one - one).unwrap(), zero, "{}", t);
+        assert_eq!((zero * one).unwrap(), zero, "{}", t);
+    }
+}
+
+#[test]
+fn parse_int_and_parse_float() {
+    assert_eq!(
+        run("print(parseInt(\"ff\", 16), parseInt(\"101\", 2), parseInt(\"-17\"), parseInt(\"  12px\"), \
+             typeof parseInt(\"7\"));")
+        .unwrap(),
+        ["255 5 -17 12 i64"]
+    );
+    assert_eq!(
+        run("print(parseFloat(\"2.5\"), parseFloat(\"-1e3\"), parseFloat(\"x\"), typeof parseFloat(\"x\"));")
+            .unwrap(),
+        ["2.5 -1000 NaN f64"]
+    );
+    for source in [
+        "print(parseInt(\"1\", 1));",
+        "print(parseInt(\"1\", 37));",
+        "print(parseInt(\"1\", 2.5));",
+        "print(parseInt(\"zz\"));",
+        "print(parseInt(\"9\", 8));",
+        "print(parseInt(5));",
+    ] {
+        assert!(
+            matches!(runtime_error(source), InterpError::InvalidArgument(_)),
+            "{:?}",
+            source
+        );
+    }
+}
+
+#[test]
+fn is_nan_and_is_finite() {
+    assert_eq!(
+        run("print(isNaN(0.0 / 0.0), isFinite(1.0 / 0.0), isFinite(-1.0 / 0.0), isNaN(1.5), isFinite(1.5));")
+            .unwrap(),
+        ["true false false false true"]
+    );
+    // Integers are never NaN and always finite; f32 works like f64.
+    assert_eq!(
+        run("let n: i32 = 5; let o: f32 = 1; let z: f32 = 0; print(isNaN(n), isFinite(n), isNaN(z / z), isFinite(o / z));")
+            .unwrap(),
+        ["false true true false"]
+    );
+    assert!(matches!(
+        runtime_error("print(isNaN(\"x\"));"),
+        InterpError::InvalidArgument(_)
+    ));
+}
+
+#[test]
+fn float_precision_fixes_the_decimals_printed() {
+    let with = |precision: Option<usize>, source: &str| {
+        let builder = TS2G::builder();
+        let builder = match precision {
+            Some(digits) => builder.float_precision(digits),
+            None => builder,
+        };
+        builder.build().evaluate(source).unwrap().printed
+    };
+    let source = "let n: u8 = 2; let g: f32 = 1; let h: f32 = 3; \
+                  print(1.0 / 3.0, 2.0, 2, n, g / h, -2.0 / 3.0, 1.0 / 0.0, 0.0 / 0.0);";
+    // By default the shortest round-tripping form: a whole float prints like an integer.
+    assert_eq!(
+        with(None, source),
+        ["0.3333333333333333 2 2 2 0.33333334 -0.6666666666666666 Infinity NaN"]
+    );
+    // A fixed precision pads whole floats too, but integer types and special values are left
+    // alone.
+    assert_eq!(
+        with(Some(3), source),
+        ["0.333 2.000 2.000 2 0.333 -0.667 Infinity NaN"]
+    );
+    assert_eq!(with(Some(0), "print(1.0 / 3.0, 2.5);"), ["0 2"]);
+    // Only print is affected; interpolation shows the value as is.
+    assert_eq!(
+        with(Some(2), "print(`${1.0 / 3.0}`, 1.0 / 3.0);"),
+        ["0.3333333333333333 0.33"]
+    );
+}
+
+#[test]
+fn scientific_notation_outside_the_plain_range() {
+    let source = "let big: u64 = 18446744073709551615; \
+                  let i: i128 = -170141183460469231731687303715884105728; \
+                  print(999999999999999900000.0, 1000000000000000000000.0, -1000000000000000000000.0, \
+                  0.000001, 0.00000099, 0.0, big, i);";
+    assert_eq!(
+        TS2G::builder()
+            .scientific(true)
+            .build()
+            .evaluate(source)
+            .unwrap()
+            .printed,
+        [
+            "999999999999999900000 1e+21 -1e+21 0.000001 9.9e-7 0 18446744073709551615 \
+             -170141183460469231731687303715884105728"
+        ]
+    );
+    // Off by default.
+    assert_eq!(
+        run(source).unwrap(),
+        [
+            "999999999999999900000 1000000000000000000000 -1000000000000000000000 0.000001 \
+             0.00000099 0 18446744073709551615 -170141183460469231731687303715884105728"
+        ]
+    );
+    // Precision applies to the mantissa.
+    assert_eq!(
+        TS2G::builder()
+            .scientific(true)
+            .float_precision(2)
+            .build()
+            .evaluate("print(1234500000000000000000000.0, 0.00000012345);")
+            .unwrap()
+            .printed,
+        ["1.23e+24 1.23e-7"]
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn programs_round_trip_through_json() {
+    let program = parse(
+        "let x: u8 = 0xff; let [a, b] = (1.5, \"s\"); \
+         for (let i = 0; i < 3; i++) { print(`${i}: ${-x as i8}`); } \
+         do { x--; } while (x > 250 && !false);",
+    )
+    .unwrap();
+    let json = serde_json::to_string(&program).unwrap();
+    let back: Vec<Box<crate::ast::Spanned<Statement>>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, program);
+}
+
+#[test]
+fn nested_templates_are_rejected() {
+    for (source, col) in [
+        ("print(`a ${`b ${1}`}`);", 12),
+        ("let s = \"x\";\nprint(`${s} ${\"`\"}`);", 16),
+    ] {
+        match run(source) {
+            Err(Ts2gError::Parse(e)) => {
+                assert_eq!(
+                    (e.col, e.msg.as_str()),
+                    (col, "backtick inside ${...}: template literals can't nest"),
+                    "{}",
+                    source
+                )
+            }
+            other => panic!("{}: {:?}", source, other),
+        }
+    }
+    // Braces and strings inside an interpolation don't end it or the template.
+    assert_eq!(run("print(`${\"}\"}-${[1, 2][1]}`);").unwrap(), ["}-2"]);
+    // To editor tooling the nested template is part of the outer one, and nothing after it is
+    // mistaken for code.
+    let tokens = crate::lexer::tokenize("`a ${`b ${1}`}` c");
+    assert_eq!(
+        tokens,
+        [
+            (crate::lexer::TokenKind::StrLiteral, 0..15),
+            (crate::lexer::TokenKind::Ident, 16..17)
+        ]
+    );
+}
+
+#[test]
+fn float_literals_adopt_f32() {
+    assert_eq!(
+        run(
+            "let y: f32 = 0.5; y = y * 2; print(y, typeof y); y = y * 2.0 + 0.25; \
+             print(y, typeof (1.5 - y), max(y, 3), typeof [y, 0.5][1], y < 3, y == 2.25);"
+        )
+        .unwrap(),
+        ["1 f32", "2.25 f32 3 f32 true true"]
+    );
+    // One past f32's range stays f64, and so no longer matches.
+    assert!(matches!(
+        runtime_error("let y: f32 = 1; print(y * 1000000000000000000000000000000000000000.0);"),
+        InterpError::TypeMismatch(l, r) if l == "f32" && r == "f64"
+    ));
+}
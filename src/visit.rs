@@ -0,0 +1,44 @@
+use crate::ast::{Expr, Node, Statement};
+
+/// A pass over the AST that produces one `Output` per node, so analyses and backends don't each
+/// write their own tree walk. By default children are visited first, in source order, and their
+/// outputs handed to `statement` or `expr`. Override `visit_*` to walk some nodes differently,
+/// calling `walk_*` for the rest.
+pub trait AstVisitor {
+    type Output;
+
+    /// Builds a statement's output from its children's, as listed by `Statement::children`.
+    fn statement(&mut self, statement: &Statement, children: Vec<Self::Output>) -> Self::Output;
+
+    /// Builds an expression's output from its children's, as listed by `Expr::children`.
+    fn expr(&mut self, expr: &Expr, children: Vec<Self::Output>) -> Self::Output;
+
+    fn visit_statement(&mut self, statement: &Statement) -> Self::Output {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) -> Self::Output {
+        walk_expr(self, expr)
+    }
+}
+
+pub fn walk_statement<V: AstVisitor + ?Sized>(visitor: &mut V, statement: &Statement) -> V::Output {
+    let children = statement
+        .children()
+        .into_iter()
+        .map(|child| match child {
+            Node::Statement(statement) => visitor.visit_statement(statement),
+            Node::Expr(expr) => visitor.visit_expr(expr),
+        })
+        .collect();
+    visitor.statement(statement, children)
+}
+
+pub fn walk_expr<V: AstVisitor + ?Sized>(visitor: &mut V, expr: &Expr) -> V::Output {
+    let children = expr
+        .children()
+        .into_iter()
+        .map(|child| visitor.visit_expr(child))
+        .collect();
+    visitor.expr(expr, children)
+}
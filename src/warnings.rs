@@ -0,0 +1,149 @@
+use crate::ast::{Expr, Span, Spanned, Statement};
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// Where the statement sits: its index in the program, then in each enclosing body.
+    pub path: Vec<usize>,
+    /// The source range of the statement the warning is about.
+    pub span: Span,
+    pub msg: String,
+}
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path: Vec<String> = self.path.iter().map(|i| (i + 1).to_string()).collect();
+        write!(f, "warning at statement {}: {}", path.join("."), self.msg)
+    }
+}
+
+struct Binding {
+    name: String,
+    path: Vec<usize>,
+    span: Span,
+    used: bool,
+    // Consts are tracked so they shadow correctly, but only lets are reported.
+    report: bool,
+}
+
+// Mirrors the interpreter's scoping: blocks, loop inits and loop bodies each get a scope, and a
+// do-while's condition is outside its body's.
+#[derive(Default)]
+struct Checker {
+    scopes: Vec<Vec<Binding>>,
+    path: Vec<usize>,
+    warnings: Vec<Warning>,
+}
+impl Checker {
+    fn body(&mut self, statements: &[Box<Spanned<Statement>>]) {
+        self.scopes.push(Vec::new());
+        for (i, statement) in statements.iter().enumerate() {
+            self.path.push(i);
+            self.statement(statement);
+            self.path.pop();
+        }
+        self.pop_scope();
+    }
+
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap() {
+            if binding.report && !binding.used && !binding.name.starts_with('_') {
+                self.warnings.push(Warning {
+                    path: binding.path,
+                    span: binding.span,
+                    msg: format!("'{}' is declared but never read", binding.name),
+                });
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, report: bool, span: Span) {
+        let binding = Binding {
+            name: name.to_owned(),
+            path: self.path.clone(),
+            span,
+            used: false,
+            report,
+        };
+        self.scopes.last_mut().unwrap().push(binding);
+    }
+
+    fn statement(&mut self, statement: &Spanned<Statement>) {
+        let span = statement.span;
+        match &statement.node {
+            Statement::ExprStatement(expr) => self.expr(expr),
+            Statement::Print(args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Statement::Let(declarators) => {
+                for (name, _, expr) in declarators {
+                    if let Some(expr) = expr {
+                        self.expr(expr);
+                    }
+                    self.declare(name, true, span);
+                }
+            }
+            Statement::Destructure(names, expr) => {
+                self.expr(expr);
+                for name in names {
+                    self.declare(name, true, span);
+                }
+            }
+            Statement::Const(name, _, expr) => {
+                self.expr(expr);
+                self.declare(name, false, span);
+            }
+            Statement::Block(statements) => self.body(statements),
+            Statement::DoWhile(body, cond) => {
+                self.body(body);
+                self.expr(cond);
+            }
+            Statement::For(init, cond, step, body) => {
+                self.scopes.push(Vec::new());
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                for expr in [cond, step].into_iter().flatten() {
+                    self.expr(expr);
+                }
+                self.body(body);
+                self.pop_scope();
+            }
+        }
+    }
+
+    // Assigning isn't reading; incrementing counts, since it reads the old value.
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Id(name)
+            | Expr::PreIncrement(name)
+            | Expr::PreDecrement(name)
+            | Expr::PostIncrement(name)
+            | Expr::PostDecrement(name) => {
+                let binding = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .flat_map(|scope| scope.iter_mut().rev())
+                    .find(|binding| binding.name == *name);
+                if let Some(binding) = binding {
+                    binding.used = true;
+                }
+            }
+            _ => {
+                for child in expr.children() {
+                    self.expr(child);
+                }
+            }
+        }
+    }
+}
+
+// Static checks that never affect execution: currently `let` bindings that are never read.
+pub fn check(stmts: &[Box<Spanned<Statement>>]) -> Vec<Warning> {
+    let mut checker = Checker::default();
+    checker.body(stmts);
+    checker.warnings.sort_by(|a, b| a.path.cmp(&b.path));
+    checker.warnings
+}